@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS seasons (
+	id SERIAL PRIMARY KEY
+	-- year in which the season ends, e.g. 2021 for the 2020-21 season
+	,year INTEGER UNIQUE NOT NULL
+	,regular_season_start DATE NOT NULL
+	,regular_season_end DATE NOT NULL
+	,play_in_start DATE NOT NULL
+	,playoffs_start DATE NOT NULL
+	,playoffs_end DATE NOT NULL
+	,is_active BOOLEAN NOT NULL DEFAULT False
+);
+
+-- there can only be one active season at a time
+CREATE UNIQUE INDEX IF NOT EXISTS seasons_one_active ON seasons(is_active) WHERE is_active;
+
+INSERT INTO seasons(year, regular_season_start, regular_season_end, play_in_start, playoffs_start, playoffs_end, is_active) VALUES
+	(2021, '2020-12-22', '2021-05-16', '2021-05-18', '2021-05-22', '2021-07-22', True)
+	ON CONFLICT DO NOTHING
+;
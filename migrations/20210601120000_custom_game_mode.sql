@@ -0,0 +1,22 @@
+INSERT INTO game_modes(id, game_mode, number_of_games) VALUES
+	(3, 'custom', 10)
+	ON CONFLICT DO NOTHING
+;
+
+-- only used by the 'custom' game mode, overrides game_modes.number_of_games for the chat
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS number_of_games INTEGER;
+
+CREATE OR REPLACE VIEW full_chat_information AS
+	SELECT
+		chats.id AS chat_id
+		,chats.game_mode AS game_mode_id
+		,chats.ranking_system AS ranking_system_id
+		,game_modes.game_mode
+		,COALESCE(chats.number_of_games, game_modes.number_of_games) AS number_of_games
+		,ranking_systems.ranking_system
+	FROM chats
+	JOIN
+	game_modes ON game_modes.id = chats.game_mode
+	JOIN
+	ranking_systems ON ranking_systems.id = chats.ranking_system
+;
@@ -0,0 +1,2 @@
+-- IANA name of the time zone that kickoff times are shown in, set with /timezone
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS time_zone TEXT NOT NULL DEFAULT 'America/New_York';
@@ -0,0 +1,5 @@
+-- set when the final standings of the week were sent to the chat automatically
+ALTER TABLE bet_weeks ADD COLUMN IF NOT EXISTS results_sent BOOLEAN NOT NULL DEFAULT False;
+
+-- weeks that ended before the results were sent automatically shouldn't be announced anymore
+UPDATE bet_weeks SET results_sent = True WHERE end_date < CURRENT_DATE;
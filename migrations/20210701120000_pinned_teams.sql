@@ -0,0 +1,6 @@
+-- teams that are part of a chat's polls every week, see /pin_team
+CREATE TABLE IF NOT EXISTS pinned_teams (
+	chat_id BIGINT REFERENCES chats(id)
+	,team_id INTEGER REFERENCES teams(id)
+	,PRIMARY KEY (chat_id, team_id)
+);
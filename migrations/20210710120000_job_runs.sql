@@ -0,0 +1,6 @@
+-- last run of every job of the scheduled tasks, used to decide which jobs are due
+CREATE TABLE IF NOT EXISTS job_runs (
+	name TEXT PRIMARY KEY
+	,last_run TIMESTAMPTZ NOT NULL
+	,succeeded BOOLEAN NOT NULL
+);
@@ -0,0 +1,88 @@
+ALTER TABLE games ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'scheduled'
+	CHECK (status IN ('scheduled', 'in_progress', 'final', 'final_ot', 'postponed', 'cancelled'));
+-- only set while the game is in progress, overtimes are period 5, 6, ...
+ALTER TABLE games ADD COLUMN IF NOT EXISTS period INTEGER;
+ALTER TABLE games ADD COLUMN IF NOT EXISTS clock TEXT;
+
+-- before there was a status, a game with points for both teams was considered finished
+UPDATE games SET status = 'final' WHERE home_points > 0 AND away_points > 0 AND date_time < NOW() - INTERVAL '4 HOURS';
+
+CREATE OR REPLACE VIEW full_game_information AS
+	SELECT 
+		games.id AS game_id
+		,games.date_time
+		,games.away_team AS away_team_id
+		,t1.name AS away_team
+		,games.away_points
+		,t1.wins AS away_wins
+		,t1.losses AS away_losses
+		,t1.srs AS srs_away
+		,ROUND(CAST(t1.wins AS DECIMAL)/greatest(t1.wins+t1.losses, 1), 3) AS win_pct_away
+		,games.home_team AS home_team_id
+		,t2.name AS home_team
+		,t2.wins AS home_wins
+		,t2.losses AS home_losses
+		,games.home_points
+		,t2.srs AS srs_home
+		,t1.srs + t2.srs AS srs_sum
+		,ROUND(CAST(t2.wins AS DECIMAL)/greatest(t2.wins+t2.losses, 1), 3) AS win_pct_home
+
+		-- define game quality as mix of (SUM OF COMBINED WINNING PERCENTAGES) and (HOW CLOSE THEIR WINNING PERCENTAGE IS TO EACH OTHER)
+		,(ROUND(CAST(t1.wins AS DECIMAL)/greatest(t1.wins+t1.losses, 1), 3) + ROUND(CAST(t2.wins AS DECIMAL)/greatest(t2.wins+t2.losses, 1), 3) ) +
+		(1 - 2 * ABS(ROUND(CAST(t1.wins AS DECIMAL)/greatest(t1.wins+t1.losses, 1), 3) - ROUND(CAST(t2.wins AS DECIMAL)/greatest(t2.wins+t2.losses, 1), 3) )) AS game_quality
+		,games.status
+		,games.period
+		,games.clock
+
+	FROM games 
+	JOIN
+	teams AS t1 ON games.away_team = t1.id
+	JOIN 
+	teams AS t2 ON games.home_team = t2.id
+
+	ORDER BY date_time ASC
+;
+
+-- only finished games have a winner, a team leading at halftime hasn't won yet
+CREATE OR REPLACE VIEW game_winners AS
+	SELECT * FROM 
+	(SELECT
+		id AS game_id
+		,CASE
+			WHEN home_points > away_points THEN home_team
+			WHEN home_points < away_points THEN away_team
+		END AS winner
+		
+	FROM games
+	WHERE status IN ('final', 'final_ot')) tmp
+	WHERE winner IS NOT NULL;
+
+CREATE OR REPLACE VIEW correct_bets_season AS
+SELECT 
+	correct_bets.user_id
+	,first_name
+	,last_name
+	,username
+	,correct_bets.chat_id
+	,finished_games
+	,COUNT(*) AS correct_bets_total
+	,RANK() OVER (
+		PARTITION BY correct_bets.chat_id
+		ORDER BY COUNT(*) DESC) AS rank_number
+	
+FROM correct_bets
+JOIN 
+	(SELECT 
+        COUNT(*) AS finished_games
+		,bet_weeks.chat_id
+        FROM
+            polls JOIN games ON games.id = polls.game_id
+            JOIN bet_weeks ON bet_weeks.id = polls.bet_week_id
+        WHERE
+            games.status IN ('final', 'final_ot')
+		GROUP BY bet_weeks.chat_id) all_games
+	ON correct_bets.chat_id = all_games.chat_id
+
+JOIN users ON users.id = correct_bets.user_id
+GROUP BY user_id, first_name, last_name, username, correct_bets.chat_id, finished_games
+;
@@ -0,0 +1,54 @@
+-- set when the game of the poll was rescheduled to a date outside of the poll's bet week,
+-- bets on voided polls don't count
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS is_void BOOLEAN NOT NULL DEFAULT False;
+
+CREATE OR REPLACE VIEW correct_bets AS
+	SELECT
+		bets.game_id
+		,bets.chat_id
+		,bets.user_id
+		,bets.bet
+		,bet_weeks.week_number
+		,bet_weeks.start_date
+		,bet_weeks.end_date
+
+	FROM bets
+	JOIN
+		game_winners ON bets.bet = game_winners.winner
+	JOIN
+		polls ON bets.poll_id = polls.id
+	JOIN bet_weeks ON polls.bet_week_id = bet_weeks.id
+	WHERE game_winners.game_id = polls.game_id
+	AND polls.is_void = False
+	;
+
+CREATE OR REPLACE VIEW correct_bets_season AS
+SELECT
+	correct_bets.user_id
+	,first_name
+	,last_name
+	,username
+	,correct_bets.chat_id
+	,finished_games
+	,COUNT(*) AS correct_bets_total
+	,RANK() OVER (
+		PARTITION BY correct_bets.chat_id
+		ORDER BY COUNT(*) DESC) AS rank_number
+
+FROM correct_bets
+JOIN
+	(SELECT
+        COUNT(*) AS finished_games
+		,bet_weeks.chat_id
+        FROM
+            polls JOIN games ON games.id = polls.game_id
+            JOIN bet_weeks ON bet_weeks.id = polls.bet_week_id
+        WHERE
+            games.status IN ('final', 'final_ot')
+            AND polls.is_void = False
+		GROUP BY bet_weeks.chat_id) all_games
+	ON correct_bets.chat_id = all_games.chat_id
+
+JOIN users ON users.id = correct_bets.user_id
+GROUP BY user_id, first_name, last_name, username, correct_bets.chat_id, finished_games
+;
@@ -0,0 +1,44 @@
+-- latest point spread of the home team, e.g. -6.5 if the home team is favoured by 6.5 points
+ALTER TABLE games ADD COLUMN IF NOT EXISTS home_spread REAL;
+
+-- spread of the home team when the poll was sent, NULL for straight up polls
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS home_spread REAL;
+
+-- chats betting against the spread get the spread in their polls
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS against_the_spread BOOLEAN NOT NULL DEFAULT False;
+
+-- the team that won the poll: the winner of the game for straight up polls, the team that
+-- covered the spread otherwise. Nobody wins a push.
+CREATE OR REPLACE VIEW poll_winners AS
+	SELECT * FROM
+	(SELECT
+		polls.id AS poll_id
+		,polls.game_id
+		,CASE
+			WHEN games.home_points + COALESCE(polls.home_spread, 0) > games.away_points THEN games.home_team
+			WHEN games.home_points + COALESCE(polls.home_spread, 0) < games.away_points THEN games.away_team
+		END AS winner
+
+	FROM polls
+	JOIN games ON games.id = polls.game_id
+	WHERE games.status IN ('final', 'final_ot')) tmp
+	WHERE winner IS NOT NULL;
+
+CREATE OR REPLACE VIEW correct_bets AS
+	SELECT
+		bets.game_id
+		,bets.chat_id
+		,bets.user_id
+		,bets.bet
+		,bet_weeks.week_number
+		,bet_weeks.start_date
+		,bet_weeks.end_date
+
+	FROM bets
+	JOIN
+		polls ON bets.poll_id = polls.id
+	JOIN
+		poll_winners ON poll_winners.poll_id = polls.id AND bets.bet = poll_winners.winner
+	JOIN bet_weeks ON polls.bet_week_id = bet_weeks.id
+	WHERE polls.is_void = False
+	;
@@ -0,0 +1,116 @@
+-- latest over/under line of the game, e.g. 221.5
+ALTER TABLE games ADD COLUMN IF NOT EXISTS total REAL;
+
+-- chats with totals polls get a second poll per game to bet on the total points
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS totals_polls BOOLEAN NOT NULL DEFAULT False;
+
+-- 'winner' polls pick a team, 'total' polls pick over or under the total of the poll
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS poll_type TEXT NOT NULL DEFAULT 'winner'
+	CHECK (poll_type IN ('winner', 'total'));
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS total REAL;
+
+-- totals bets have no team in bets.bet, over is True for the over and False for the under
+ALTER TABLE bets ADD COLUMN IF NOT EXISTS bet_type TEXT NOT NULL DEFAULT 'winner'
+	CHECK (bet_type IN ('winner', 'total'));
+ALTER TABLE bets ADD COLUMN IF NOT EXISTS over BOOLEAN;
+CREATE UNIQUE INDEX IF NOT EXISTS bets_one_total_bet ON bets(chat_id, user_id, poll_id) WHERE bet_type = 'total';
+
+CREATE OR REPLACE VIEW poll_winners AS
+	SELECT * FROM
+	(SELECT
+		polls.id AS poll_id
+		,polls.game_id
+		,CASE
+			WHEN games.home_points + COALESCE(polls.home_spread, 0) > games.away_points THEN games.home_team
+			WHEN games.home_points + COALESCE(polls.home_spread, 0) < games.away_points THEN games.away_team
+		END AS winner
+
+	FROM polls
+	JOIN games ON games.id = polls.game_id
+	WHERE games.status IN ('final', 'final_ot')
+	AND polls.poll_type = 'winner') tmp
+	WHERE winner IS NOT NULL;
+
+CREATE OR REPLACE VIEW correct_bets_season AS
+SELECT
+	correct_bets.user_id
+	,first_name
+	,last_name
+	,username
+	,correct_bets.chat_id
+	,finished_games
+	,COUNT(*) AS correct_bets_total
+	,RANK() OVER (
+		PARTITION BY correct_bets.chat_id
+		ORDER BY COUNT(*) DESC) AS rank_number
+
+FROM correct_bets
+JOIN
+	(SELECT
+        COUNT(*) AS finished_games
+		,bet_weeks.chat_id
+        FROM
+            polls JOIN games ON games.id = polls.game_id
+            JOIN bet_weeks ON bet_weeks.id = polls.bet_week_id
+        WHERE
+            games.status IN ('final', 'final_ot')
+            AND polls.is_void = False
+            AND polls.poll_type = 'winner'
+		GROUP BY bet_weeks.chat_id) all_games
+	ON correct_bets.chat_id = all_games.chat_id
+
+JOIN users ON users.id = correct_bets.user_id
+GROUP BY user_id, first_name, last_name, username, correct_bets.chat_id, finished_games
+;
+
+-- nobody wins a push
+CREATE OR REPLACE VIEW correct_total_bets AS
+	SELECT
+		bets.game_id
+		,bets.chat_id
+		,bets.user_id
+		,bets.over
+		,bet_weeks.week_number
+		,bet_weeks.start_date
+		,bet_weeks.end_date
+
+	FROM bets
+	JOIN
+		polls ON bets.poll_id = polls.id
+	JOIN
+		games ON games.id = polls.game_id
+	JOIN bet_weeks ON polls.bet_week_id = bet_weeks.id
+	WHERE bets.bet_type = 'total'
+	AND polls.is_void = False
+	AND games.status IN ('final', 'final_ot')
+	AND games.home_points + games.away_points <> polls.total
+	AND (games.home_points + games.away_points > polls.total) = bets.over
+	;
+
+-- users who only bet on totals didn't bet on a winner, so they have no weekly points either
+CREATE OR REPLACE VIEW user_with_no_correct_bets_week AS
+SELECT
+	DISTINCT users.id
+	,bet_weeks.week_number
+	,0 as correct_bets_week
+	,bets.chat_id
+	,bet_weeks.start_date
+	,bet_weeks.end_date
+
+FROM 
+    users 
+JOIN bets ON users.id = bets.user_id AND bets.bet_type = 'winner'
+JOIN bet_weeks ON bets.chat_id = bet_weeks.chat_id
+	
+WHERE users.id NOT IN 
+	(SELECT	
+		users.id 
+	FROM  
+	 users 
+	JOIN 
+		correct_bets ON users.id = correct_bets.user_id 
+	WHERE
+		correct_bets.week_number = bet_weeks.week_number
+		AND correct_bets.chat_id = bet_weeks.chat_id
+ )  
+;
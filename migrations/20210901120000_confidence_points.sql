@@ -0,0 +1,65 @@
+INSERT INTO ranking_systems(id, ranking_system) VALUES
+	(3, 'confidence')
+	ON CONFLICT DO NOTHING
+;
+
+-- how confident a user is in their pick of a poll, from 1 to the number of polls of the week.
+-- A correct pick earns its confidence value.
+CREATE TABLE IF NOT EXISTS confidence_picks (
+	id SERIAL PRIMARY KEY
+	,chat_id BIGINT REFERENCES chats(id)
+	,user_id BIGINT REFERENCES users(id)
+	,poll_id TEXT REFERENCES polls(id)
+	,bet_week_id INTEGER REFERENCES bet_weeks(id)
+	,confidence INTEGER NOT NULL CHECK (confidence > 0)
+	,UNIQUE(user_id, poll_id)
+	,UNIQUE(user_id, bet_week_id, confidence)
+);
+
+-- picks without a confidence value are worth nothing
+CREATE OR REPLACE VIEW weekly_confidence_rankings AS
+SELECT
+	users.id
+	,users.first_name
+	,users.last_name
+	,users.username
+	,week_number
+	,confidence_points_week
+	,chat_id
+	,start_date
+	,end_date
+	,RANK() OVER (
+			PARTITION BY chat_id, week_number
+			ORDER BY confidence_points_week DESC
+		) rank_number
+
+FROM
+	users
+JOIN
+	(SELECT
+		bets.user_id
+		,bet_weeks.week_number
+		,SUM(CASE WHEN poll_winners.winner = bets.bet THEN COALESCE(confidence_picks.confidence, 0) ELSE 0 END) AS confidence_points_week
+		,bets.chat_id
+		,bet_weeks.start_date
+		,bet_weeks.end_date
+	FROM bets
+	JOIN
+		polls ON polls.id = bets.poll_id
+	JOIN
+		bet_weeks ON bet_weeks.id = polls.bet_week_id
+	LEFT JOIN
+		poll_winners ON poll_winners.poll_id = polls.id
+	LEFT JOIN
+		confidence_picks ON confidence_picks.poll_id = polls.id AND confidence_picks.user_id = bets.user_id
+	WHERE bets.bet_type = 'winner'
+	AND polls.is_void = False
+	GROUP BY
+		bets.user_id
+		,bets.chat_id
+		,bet_weeks.week_number
+		,bet_weeks.start_date
+		,bet_weeks.end_date
+	) AS tmp
+	ON users.id = tmp.user_id
+;
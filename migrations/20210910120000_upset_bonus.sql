@@ -0,0 +1,66 @@
+INSERT INTO ranking_systems(id, ranking_system) VALUES
+	(4, 'upset_bonus')
+	ON CONFLICT DO NOTHING
+;
+
+-- strength of both teams when the poll was sent, so later scrapes don't change the scoring
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS away_srs NUMERIC;
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS home_srs NUMERIC;
+
+-- a correct pick is worth one point, picking the underdog correctly earns a bonus of
+-- one point for every 5 points of SRS the underdog was behind
+CREATE OR REPLACE VIEW weekly_weighted_rankings AS
+SELECT
+	users.id
+	,users.first_name
+	,users.last_name
+	,users.username
+	,week_number
+	,weighted_points_week
+	,chat_id
+	,start_date
+	,end_date
+	,RANK() OVER (
+			PARTITION BY chat_id, week_number
+			ORDER BY weighted_points_week DESC
+		) rank_number
+
+FROM
+	users
+JOIN
+	(SELECT
+		bets.user_id
+		,bet_weeks.week_number
+		,ROUND(SUM(
+			CASE WHEN poll_winners.winner = bets.bet THEN
+				1 + GREATEST(
+					CASE
+						WHEN bets.bet = games.home_team THEN polls.away_srs - polls.home_srs
+						ELSE polls.home_srs - polls.away_srs
+					END
+				, 0) / 5
+			ELSE 0 END
+		), 1) AS weighted_points_week
+		,bets.chat_id
+		,bet_weeks.start_date
+		,bet_weeks.end_date
+	FROM bets
+	JOIN
+		polls ON polls.id = bets.poll_id
+	JOIN
+		games ON games.id = polls.game_id
+	JOIN
+		bet_weeks ON bet_weeks.id = polls.bet_week_id
+	LEFT JOIN
+		poll_winners ON poll_winners.poll_id = polls.id
+	WHERE bets.bet_type = 'winner'
+	AND polls.is_void = False
+	GROUP BY
+		bets.user_id
+		,bets.chat_id
+		,bet_weeks.week_number
+		,bet_weeks.start_date
+		,bet_weeks.end_date
+	) AS tmp
+	ON users.id = tmp.user_id
+;
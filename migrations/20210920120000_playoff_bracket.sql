@@ -0,0 +1,75 @@
+-- chats playing the bracket challenge get a poll for every playoff series
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS bracket_challenge BOOLEAN NOT NULL DEFAULT False;
+
+-- round 1 is the first round, round 4 the finals
+CREATE TABLE IF NOT EXISTS playoff_series (
+	id SERIAL PRIMARY KEY
+	,season_id INTEGER REFERENCES seasons(id)
+	,round INTEGER NOT NULL CHECK (round BETWEEN 1 AND 4)
+	-- the team with the lower id is always team_1, so every series is only stored once
+	,team_1 INTEGER REFERENCES teams(id)
+	,team_2 INTEGER REFERENCES teams(id)
+	,team_1_wins INTEGER NOT NULL DEFAULT 0
+	,team_2_wins INTEGER NOT NULL DEFAULT 0
+	,UNIQUE(season_id, round, team_1, team_2)
+);
+
+CREATE TABLE IF NOT EXISTS series_polls (
+	id TEXT PRIMARY KEY
+	,local_id INT
+	,chat_id BIGINT REFERENCES chats(id)
+	,series_id INTEGER REFERENCES playoff_series(id)
+	,is_open BOOLEAN NOT NULL DEFAULT True
+	,UNIQUE(chat_id, series_id)
+);
+
+CREATE TABLE IF NOT EXISTS series_bets (
+	id SERIAL PRIMARY KEY
+	,chat_id BIGINT REFERENCES chats(id)
+	,user_id BIGINT REFERENCES users(id)
+	,poll_id TEXT REFERENCES series_polls(id)
+	,series_id INTEGER REFERENCES playoff_series(id)
+	,winner INTEGER REFERENCES teams(id)
+	,number_of_games INTEGER NOT NULL CHECK (number_of_games BETWEEN 4 AND 7)
+	,UNIQUE(user_id, poll_id)
+);
+
+-- every round is worth twice as much as the one before
+CREATE OR REPLACE VIEW series_results AS
+	SELECT
+		id AS series_id
+		,season_id
+		,round
+		,CASE
+			WHEN team_1_wins = 4 THEN team_1
+			WHEN team_2_wins = 4 THEN team_2
+		END AS winner
+		,team_1_wins + team_2_wins AS number_of_games
+		,CAST(POWER(2, round - 1) AS INTEGER) AS weight
+	FROM playoff_series
+	WHERE team_1_wins = 4 OR team_2_wins = 4
+;
+
+-- the right winner earns the weight of the round, the right number of games on top doubles it
+CREATE OR REPLACE VIEW bracket_points AS
+	SELECT
+		series_bets.chat_id
+		,series_bets.user_id
+		,series_results.season_id
+		,series_results.round
+		,SUM(
+			CASE WHEN series_bets.winner = series_results.winner THEN
+				CASE WHEN series_bets.number_of_games = series_results.number_of_games
+					THEN 2 * series_results.weight
+					ELSE series_results.weight
+				END
+			ELSE 0 END
+		) AS points
+	FROM series_bets
+	JOIN series_results ON series_results.series_id = series_bets.series_id
+	GROUP BY
+		series_bets.chat_id
+		,series_bets.user_id
+		,series_results.season_id
+		,series_results.round
+;
@@ -0,0 +1,6 @@
+-- chats with keyboard bets get every game as a message with a button for each team instead of a poll
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS keyboard_bets BOOLEAN NOT NULL DEFAULT False;
+
+-- these messages are stored as polls as well, so their bets count like any other.
+-- The id is 'keyboard <chat_id> <message_id>' and local_id the id of the message.
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS is_keyboard BOOLEAN NOT NULL DEFAULT False;
@@ -0,0 +1,731 @@
+use async_trait::async_trait;
+use basketball_betting_bot::utils::GameStatus;
+use chrono::{DateTime, FixedOffset};
+use log::warn;
+use scraper::{Html, Selector};
+use select::document::Document;
+use select::predicate::Class;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+/// Where the scheduled tasks get the NBA data from.
+/// The scrapers in scrape.rs only talk to this trait and write what they get into the db.
+#[async_trait]
+pub trait DataSource: Send + Sync {
+    /// standings of all teams of the season ending in year
+    async fn team_standings(&self, year: i32) -> anyhow::Result<Vec<TeamStanding>>;
+
+    /// all games of the given month (e.g. "january") of the season ending in year,
+    /// games that weren't played yet have 0 points and no status
+    async fn schedule(&self, year: i32, month: &str) -> anyhow::Result<Vec<ScheduledGame>>;
+
+    /// scores, point spreads and over/under lines of the games of the last and the upcoming days, updated during
+    /// the games
+    async fn live_scores(&self) -> anyhow::Result<Vec<LiveScore>>;
+
+    /// all playoff series of the season ending in year that have a matchup already
+    async fn playoff_series(&self, year: i32) -> anyhow::Result<Vec<PlayoffSeries>>;
+}
+
+/// Reads SCRAPE_FIXTURES_DIR: if it's set the data is read from the fixture files in that
+/// directory, otherwise it's scraped from the web
+pub fn from_env() -> Box<dyn DataSource> {
+    match env::var("SCRAPE_FIXTURES_DIR") {
+        Ok(dir) => Box::new(FixtureSource::new(dir)),
+        Err(_) => Box::new(WebSource),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TeamStanding {
+    pub name: String,
+    pub wins: i32,
+    pub losses: i32,
+    pub srs: sqlx::types::BigDecimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledGame {
+    pub date: DateTime<FixedOffset>,
+    pub away_team: String,
+    pub away_points: i32,
+    pub home_team: String,
+    pub home_points: i32,
+    /// None if the source doesn't know anything new about the game's status
+    pub status: Option<GameStatus>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LiveScore {
+    pub date: DateTime<FixedOffset>,
+    pub away_team: String,
+    pub away_points: i32,
+    pub home_team: String,
+    pub home_points: i32,
+    /// None if the source doesn't know anything new about the game's status
+    pub status: Option<GameStatus>,
+    /// point spread of the home team, None if there's no line for the game (yet)
+    pub home_spread: Option<f32>,
+    /// over/under line, None if there's no line for the game (yet)
+    pub total: Option<f32>,
+    pub last_updated: DateTime<FixedOffset>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayoffSeries {
+    /// 1 for the first round up to 4 for the finals
+    pub round: i32,
+    pub team_1: String,
+    pub team_1_wins: i32,
+    pub team_2: String,
+    pub team_2_wins: i32,
+}
+
+/// Standings, schedule and playoff series from basketball-reference.com, live scores from covers.com
+pub struct WebSource;
+
+#[async_trait]
+impl DataSource for WebSource {
+    async fn team_standings(&self, year: i32) -> anyhow::Result<Vec<TeamStanding>> {
+        let link = format!(
+            "https://www.basketball-reference.com/leagues/NBA_{year}.html",
+            year = year
+        );
+        let resp = reqwest::get(&link).await?.text().await?;
+        parse_team_standings(&resp)
+    }
+
+    async fn schedule(&self, year: i32, month: &str) -> anyhow::Result<Vec<ScheduledGame>> {
+        let link = format!(
+            "https://www.basketball-reference.com/leagues/NBA_{year}_games-{month}.html",
+            year = year,
+            month = month
+        );
+        let resp = reqwest::get(&link).await?.text().await?;
+        parse_schedule(&resp)
+    }
+
+    async fn live_scores(&self) -> anyhow::Result<Vec<LiveScore>> {
+        let link = String::from("https://www.covers.com/sports/nba/matchups");
+        let resp = reqwest::get(&link).await?.text().await?;
+        parse_live_scores(&resp)
+    }
+
+    async fn playoff_series(&self, year: i32) -> anyhow::Result<Vec<PlayoffSeries>> {
+        let link = format!(
+            "https://www.basketball-reference.com/playoffs/NBA_{year}.html",
+            year = year
+        );
+        let resp = reqwest::get(&link).await?.text().await?;
+        parse_playoff_series(&resp)
+    }
+}
+
+/// Reads pages that were saved from the web sources before, so the scrapers can run without
+/// network access. The files are named like the pages they were saved from:
+/// NBA_{year}.html, NBA_{year}_games-{month}.html, playoffs_NBA_{year}.html and matchups.html
+pub struct FixtureSource {
+    dir: PathBuf,
+}
+
+impl FixtureSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn read(&self, file_name: &str) -> anyhow::Result<String> {
+        Ok(std::fs::read_to_string(self.dir.join(file_name))?)
+    }
+}
+
+#[async_trait]
+impl DataSource for FixtureSource {
+    async fn team_standings(&self, year: i32) -> anyhow::Result<Vec<TeamStanding>> {
+        parse_team_standings(&self.read(&format!("NBA_{}.html", year))?)
+    }
+
+    async fn schedule(&self, year: i32, month: &str) -> anyhow::Result<Vec<ScheduledGame>> {
+        // there are no fixtures for most months, that's the same as a month without games
+        match self.read(&format!("NBA_{}_games-{}.html", year, month)) {
+            Ok(page) => parse_schedule(&page),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    async fn live_scores(&self) -> anyhow::Result<Vec<LiveScore>> {
+        parse_live_scores(&self.read("matchups.html")?)
+    }
+
+    async fn playoff_series(&self, year: i32) -> anyhow::Result<Vec<PlayoffSeries>> {
+        // no fixture means the playoffs didn't start yet
+        match self.read(&format!("playoffs_NBA_{}.html", year)) {
+            Ok(page) => parse_playoff_series(&page),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+fn parse_team_standings(page: &str) -> anyhow::Result<Vec<TeamStanding>> {
+    let doc = Html::parse_document(page);
+    let selector = Selector::parse("tr").unwrap();
+    let mut standings = Vec::new();
+
+    for entry in doc.select(&selector) {
+        let td = entry.text().collect::<Vec<_>>();
+
+        // if the row has 8 or 9 entries, it's a team description
+        if vec![8, 9, 10].contains(&td.len()) {
+            let name = td[0];
+            let srs = td[td.len() - 1].parse::<sqlx::types::BigDecimal>();
+            let wins = td[td.len() - 7].parse::<i32>();
+            let losses = td[td.len() - 6].parse::<i32>();
+            match (srs, wins, losses) {
+                (Ok(srs), Ok(wins), Ok(losses)) => standings.push(TeamStanding {
+                    name: name.to_string(),
+                    wins,
+                    losses,
+                    srs,
+                }),
+                _ => warn!("skipped the standings row {:?}", td),
+            }
+        }
+    }
+
+    Ok(standings)
+}
+
+fn parse_schedule(page: &str) -> anyhow::Result<Vec<ScheduledGame>> {
+    let doc = Html::parse_document(page);
+    let selector = Selector::parse("tr").unwrap();
+    let mut games = Vec::new();
+
+    for entry in doc.select(&selector) {
+        let td = entry.text().collect::<Vec<_>>();
+        // every game has at least a date, a time and both teams
+        if td.len() < 4 {
+            continue;
+        }
+        let date = td[0];
+
+        let time = td[1].replace("0p", "0:00 pm -0500");
+        let dtg = format!("{} {}", date, time);
+        let dt = DateTime::parse_from_str(&dtg, "%a, %b %d, %Y %I:%M:%S %P %z");
+
+        // if we can't parse the date it almost certainly means the row is a header and not a game
+        let date = match dt {
+            Ok(date) => date,
+            Err(e) => {
+                warn!("skipped the row with error {}", e);
+                continue;
+            }
+        };
+
+        let away_team = td[2].to_string();
+
+        // postponed games are marked in the notes column and weren't played
+        if td.iter().any(|entry| entry.contains("Postponed")) {
+            games.push(ScheduledGame {
+                date,
+                away_team,
+                away_points: 0,
+                home_team: td[3].to_string(),
+                home_points: 0,
+                status: Some(GameStatus::Postponed),
+            });
+            continue;
+        }
+
+        // if row has 4 entries, the game was not played yet
+        let game = match td.len() {
+            4 => ScheduledGame {
+                date,
+                away_team,
+                away_points: 0,
+                home_team: td[3].to_string(),
+                home_points: 0,
+                status: None,
+            },
+            // only finished games have points, overtimes are marked with OT, 2OT, ...
+            len if len >= 6 => ScheduledGame {
+                date,
+                away_team,
+                away_points: td[3].parse().unwrap_or(0),
+                home_team: td[4].to_string(),
+                home_points: td[5].parse().unwrap_or(0),
+                status: match td.iter().any(|entry| entry.ends_with("OT")) {
+                    true => Some(GameStatus::FinalOt),
+                    false => Some(GameStatus::Final),
+                },
+            },
+            _ => {
+                warn!("skipped the schedule row {:?}", td);
+                continue;
+            }
+        };
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+/// Attribute of a game on the covers.com matchups page with the point spread of the home team,
+/// e.g. "-6.5". The name is an assumption that wasn't checked against the live page yet, the
+/// fixture only mirrors it. If it's wrong no game gets a line: parse_live_scores logs a warning and
+/// the chats betting against the spread are told that it's turned off (see spreads_available).
+const COVERS_HOME_SPREAD_ATTRIBUTE: &str = "data-home-spread";
+
+/// Attribute with the over/under line of a game, e.g. "221.5". Like the spread attribute it's
+/// an unverified assumption, without it the totals polls fall back to the average points of both
+/// teams (see get_total) and say so in their question.
+const COVERS_TOTAL_ATTRIBUTE: &str = "data-total";
+
+fn parse_live_scores(page: &str) -> anyhow::Result<Vec<LiveScore>> {
+    let full_names = [
+        "Atlanta Hawks".to_string(),
+        "Boston Celtics".to_string(),
+        "Brooklyn Nets".to_string(),
+        "Charlotte Hornets".to_string(),
+        "Chicago Bulls".to_string(),
+        "Cleveland Cavaliers".to_string(),
+        "Dallas Mavericks".to_string(),
+        "Denver Nuggets".to_string(),
+        "Detroit Pistons".to_string(),
+        "Golden State Warriors".to_string(),
+        "Houston Rockets".to_string(),
+        "Indiana Pacers".to_string(),
+        "Los Angeles Clippers".to_string(),
+        "Los Angeles Lakers".to_string(),
+        "Memphis Grizzlies".to_string(),
+        "Miami Heat".to_string(),
+        "Milwaukee Bucks".to_string(),
+        "Minnesota Timberwolves".to_string(),
+        "New Orleans Pelicans".to_string(),
+        "New York Knicks".to_string(),
+        "Oklahoma City Thunder".to_string(),
+        "Orlando Magic".to_string(),
+        "Philadelphia 76ers".to_string(),
+        "Phoenix Suns".to_string(),
+        "Portland Trail Blazers".to_string(),
+        "Sacramento Kings".to_string(),
+        "San Antonio Spurs".to_string(),
+        "Toronto Raptors".to_string(),
+        "Utah Jazz".to_string(),
+        "Washington Wizards".to_string(),
+    ];
+
+    let short_names = [
+        "ATL".to_string(),
+        "BOS".to_string(),
+        "BK".to_string(),
+        "CHA".to_string(),
+        "CHI".to_string(),
+        "CLE".to_string(),
+        "DAL".to_string(),
+        "DEN".to_string(),
+        "DET".to_string(),
+        "GS".to_string(),
+        "HOU".to_string(),
+        "IND".to_string(),
+        "LAC".to_string(),
+        "LAL".to_string(),
+        "MEM".to_string(),
+        "MIA".to_string(),
+        "MIL".to_string(),
+        "MIN".to_string(),
+        "NO".to_string(),
+        "NY".to_string(),
+        "OKC".to_string(),
+        "ORL".to_string(),
+        "PHI".to_string(),
+        "PHO".to_string(),
+        "POR".to_string(),
+        "SAC".to_string(),
+        "SA".to_string(),
+        "TOR".to_string(),
+        "UTA".to_string(),
+        "WAS".to_string(),
+    ];
+    let short_name_to_full_name: HashMap<_, _> = short_names
+        .iter()
+        .map(String::as_str)
+        .zip(full_names.iter())
+        .collect();
+
+    let document = Document::from(page);
+    let mut scores = Vec::new();
+
+    for node in document.find(Class("cmg_game_data")) {
+        let home_points = node.attr("data-home-score");
+        if home_points.is_none() {
+            continue;
+        }
+        let points = (
+            home_points.unwrap_or("0").parse::<i32>(),
+            node.attr("data-away-score").unwrap_or("0").parse::<i32>(),
+        );
+        let (home_points, away_points) = match points {
+            (Ok(home_points), Ok(away_points)) => (home_points, away_points),
+            _ => {
+                warn!(
+                    "skipped a game with a malformed score: {:?} - {:?}",
+                    node.attr("data-away-score"),
+                    node.attr("data-home-score")
+                );
+                continue;
+            }
+        };
+        //2021-01-05T22:47:51.0000000
+        let last_updated = match chrono::DateTime::parse_from_rfc3339(&format!(
+            "{date}{offset}",
+            date = node
+                .attr("data-last-update")
+                .unwrap_or("2000-01-01T00:00:00"),
+            offset = "-04:00"
+        )) {
+            Ok(last_updated) => last_updated,
+            Err(e) => {
+                warn!("skipped a game with a malformed last update: {}", e);
+                continue;
+            }
+        };
+        let game_date = match chrono::DateTime::parse_from_str(
+            &format!(
+                "{date}{offset}",
+                date = node.attr("data-game-date").unwrap_or("2000-01-01 00:00:00"),
+                offset = "-0500"
+            ),
+            "%Y-%m-%d %H:%M:%S %z",
+        ) {
+            Ok(game_date) => game_date,
+            Err(e) => {
+                warn!("skipped a game with error {}", e);
+                continue;
+            }
+        };
+        // teams we don't know would end up as games nobody can find
+        let team = |attribute: &str| {
+            node.attr(attribute)
+                .and_then(|short_name| short_name_to_full_name.get(short_name))
+                .map(|full_name| full_name.to_string())
+        };
+        let (away_team, home_team) = match (
+            team("data-away-team-shortname-search"),
+            team("data-home-team-shortname-search"),
+        ) {
+            (Some(away_team), Some(home_team)) => (away_team, home_team),
+            _ => {
+                warn!(
+                    "skipped a game with an unknown team: {:?} @ {:?}",
+                    node.attr("data-away-team-shortname-search"),
+                    node.attr("data-home-team-shortname-search")
+                );
+                continue;
+            }
+        };
+        let status = node
+            .find(Class("cmg_matchup_list_status"))
+            .next()
+            .and_then(|status| parse_covers_status(&status.text()));
+        let home_spread = node
+            .attr(COVERS_HOME_SPREAD_ATTRIBUTE)
+            .and_then(|spread| spread.parse::<f32>().ok());
+        let total = node
+            .attr(COVERS_TOTAL_ATTRIBUTE)
+            .and_then(|total| total.parse::<f32>().ok());
+
+        scores.push(LiveScore {
+            date: game_date,
+            away_team,
+            away_points,
+            home_team,
+            home_points,
+            status,
+            home_spread,
+            total,
+            last_updated,
+        });
+    }
+
+    // covers.com has a line for nearly every upcoming game, none at all means the markup changed
+    let has_upcoming_games = scores
+        .iter()
+        .any(|score| score.status == Some(GameStatus::Scheduled));
+    if has_upcoming_games && scores.iter().all(|score| score.home_spread.is_none()) {
+        warn!(
+            "no point spreads found on covers.com, check COVERS_HOME_SPREAD_ATTRIBUTE ({})",
+            COVERS_HOME_SPREAD_ATTRIBUTE
+        );
+    }
+    if has_upcoming_games && scores.iter().all(|score| score.total.is_none()) {
+        warn!(
+            "no over/under lines found on covers.com, check COVERS_TOTAL_ATTRIBUTE ({})",
+            COVERS_TOTAL_ATTRIBUTE
+        );
+    }
+
+    Ok(scores)
+}
+
+/// Rows look like "Eastern Conference First Round | Milwaukee Bucks over Miami Heat (4-0)",
+/// ongoing series like "Phoenix Suns lead Los Angeles Lakers (2-1)"
+fn parse_playoff_series(page: &str) -> anyhow::Result<Vec<PlayoffSeries>> {
+    let doc = Html::parse_document(page);
+    let rows = Selector::parse("tr").unwrap();
+    let cells = Selector::parse("td").unwrap();
+    let links = Selector::parse("a").unwrap();
+    let mut series = Vec::new();
+
+    for row in doc.select(&rows) {
+        let td = row.select(&cells).collect::<Vec<_>>();
+        if td.len() < 2 {
+            continue;
+        }
+
+        let round_name = td[0].text().collect::<String>();
+        let round = match playoff_round(round_name.trim()) {
+            Some(round) => round,
+            None => continue,
+        };
+
+        let teams = td[1]
+            .select(&links)
+            .map(|team| team.text().collect::<String>())
+            .collect::<Vec<_>>();
+        if teams.len() < 2 {
+            continue;
+        }
+
+        // the wins are in the same order as the teams
+        let text = td[1].text().collect::<String>();
+        let score = text
+            .rsplit('(')
+            .next()
+            .and_then(|score| score.split(')').next())
+            .unwrap_or("0-0");
+        let mut wins = score
+            .split('-')
+            .map(|wins| wins.trim().parse::<i32>().unwrap_or(0));
+
+        series.push(PlayoffSeries {
+            round,
+            team_1: teams[0].to_owned(),
+            team_1_wins: wins.next().unwrap_or(0),
+            team_2: teams[1].to_owned(),
+            team_2_wins: wins.next().unwrap_or(0),
+        });
+    }
+
+    Ok(series)
+}
+
+fn playoff_round(name: &str) -> Option<i32> {
+    if name == "Finals" {
+        Some(4)
+    } else if name.ends_with("Conference Finals") {
+        Some(3)
+    } else if name.ends_with("Semifinals") {
+        Some(2)
+    } else if name.ends_with("First Round") {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// covers.com shows e.g. "Final", "Final/OT", "3rd 5:32", "Half", "Postponed" or the tip-off time
+fn parse_covers_status(status: &str) -> Option<GameStatus> {
+    let status = status.trim().to_lowercase();
+    let mut words = status.split_whitespace();
+    let first_word = words.next()?;
+    let clock = words.next().unwrap_or_default().to_string();
+
+    if first_word.starts_with("final") {
+        return match first_word.contains("ot") {
+            true => Some(GameStatus::FinalOt),
+            false => Some(GameStatus::Final),
+        };
+    }
+
+    let period = match first_word {
+        "postponed" => return Some(GameStatus::Postponed),
+        "canceled" | "cancelled" => return Some(GameStatus::Cancelled),
+        "half" | "halftime" => {
+            return Some(GameStatus::InProgress {
+                period: 2,
+                clock: "Half".to_string(),
+            })
+        }
+        "1st" => 1,
+        "2nd" => 2,
+        "3rd" => 3,
+        "4th" => 4,
+        "ot" => 5,
+        // 2OT, 3OT, ...
+        overtime if overtime.ends_with("ot") => {
+            4 + overtime.trim_end_matches("ot").parse::<i32>().ok()?
+        }
+        // the tip-off time, the game didn't start yet
+        _ => return Some(GameStatus::Scheduled),
+    };
+
+    Some(GameStatus::InProgress { period, clock })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(file_name: &str) -> String {
+        FixtureSource::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"))
+            .read(file_name)
+            .unwrap()
+    }
+
+    #[test]
+    fn team_standings_from_fixture() {
+        let standings = parse_team_standings(&fixture("NBA_2021.html")).unwrap();
+        assert_eq!(standings.len(), 7);
+
+        let jazz = standings
+            .iter()
+            .find(|team| team.name == "Utah Jazz")
+            .unwrap();
+        assert_eq!((jazz.wins, jazz.losses), (52, 20));
+        assert_eq!(jazz.srs, "8.97".parse().unwrap());
+    }
+
+    #[test]
+    fn team_standings_skip_malformed_rows() {
+        let page = "<table><tr><th>Boston Celtics</th><td>x</td><td>36</td><td>.500</td><td>13.0</td><td>112.6</td><td>111.2</td><td>n/a</td></tr></table>";
+        assert!(parse_team_standings(page).unwrap().is_empty());
+    }
+
+    #[test]
+    fn schedule_from_fixture() {
+        let games = parse_schedule(&fixture("NBA_2021_games-january.html")).unwrap();
+        assert_eq!(games.len(), 4);
+
+        let finished = &games[0];
+        assert_eq!(finished.date.to_rfc3339(), "2021-01-04T19:30:00-05:00");
+        assert_eq!(finished.away_team, "Milwaukee Bucks");
+        assert_eq!(finished.home_team, "Boston Celtics");
+        assert_eq!((finished.away_points, finished.home_points), (121, 119));
+        assert_eq!(finished.status, Some(GameStatus::Final));
+
+        let upcoming = &games[3];
+        assert_eq!(upcoming.date.to_rfc3339(), "2021-01-06T22:00:00-05:00");
+        assert_eq!(upcoming.away_team, "Detroit Pistons");
+        assert_eq!(upcoming.home_team, "Los Angeles Lakers");
+        assert_eq!((upcoming.away_points, upcoming.home_points), (0, 0));
+        assert_eq!(upcoming.status, None);
+    }
+
+    #[test]
+    fn schedule_marks_postponed_games() {
+        let page = "<table><tr><th>Wed, Jan 6, 2021</th><td>7:30p</td><td>Miami Heat</td><td></td><td>Boston Celtics</td><td></td><td></td><td></td><td></td><td>Postponed</td></tr></table>";
+        let games = parse_schedule(page).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].home_team, "Boston Celtics");
+        assert_eq!(games[0].status, Some(GameStatus::Postponed));
+    }
+
+    #[test]
+    fn live_scores_from_fixture() {
+        let scores = parse_live_scores(&fixture("matchups.html")).unwrap();
+        assert_eq!(scores.len(), 3);
+
+        let finished = &scores[0];
+        assert_eq!(finished.away_team, "Miami Heat");
+        assert_eq!(finished.home_team, "Boston Celtics");
+        assert_eq!((finished.away_points, finished.home_points), (104, 110));
+        assert_eq!(finished.status, Some(GameStatus::Final));
+        assert_eq!(finished.home_spread, None);
+
+        let in_progress = &scores[1];
+        assert_eq!(in_progress.date.to_rfc3339(), "2021-01-06T22:00:00-05:00");
+        assert_eq!(
+            in_progress.status,
+            Some(GameStatus::InProgress {
+                period: 1,
+                clock: "4:12".to_string()
+            })
+        );
+
+        let upcoming = &scores[2];
+        assert_eq!(upcoming.away_team, "Chicago Bulls");
+        assert_eq!(upcoming.home_team, "Milwaukee Bucks");
+        assert_eq!(upcoming.status, Some(GameStatus::Scheduled));
+    }
+
+    #[test]
+    fn live_scores_skip_unknown_teams() {
+        let page = r#"<div class="cmg_game_data" data-home-score="0" data-away-score="0" data-last-update="2021-01-06T22:47:51.0000000" data-game-date="2021-01-08 20:00:00" data-home-team-shortname-search="XYZ" data-away-team-shortname-search="CHI"></div>"#;
+        assert!(parse_live_scores(page).unwrap().is_empty());
+    }
+
+    #[test]
+    fn live_scores_skip_malformed_rows() {
+        let bad_score = r#"<div class="cmg_game_data" data-home-score="n/a" data-away-score="0" data-last-update="2021-01-06T22:47:51.0000000" data-game-date="2021-01-08 20:00:00" data-home-team-shortname-search="MIL" data-away-team-shortname-search="CHI"></div>"#;
+        let bad_update = r#"<div class="cmg_game_data" data-home-score="0" data-away-score="0" data-last-update="yesterday" data-game-date="2021-01-08 20:00:00" data-home-team-shortname-search="MIL" data-away-team-shortname-search="CHI"></div>"#;
+        let good = r#"<div class="cmg_game_data" data-home-score="0" data-away-score="0" data-last-update="2021-01-06T22:47:51.0000000" data-game-date="2021-01-08 20:00:00" data-home-team-shortname-search="MIL" data-away-team-shortname-search="CHI"></div>"#;
+
+        let scores = parse_live_scores(&format!("{}{}{}", bad_score, bad_update, good)).unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].home_team, "Milwaukee Bucks");
+    }
+
+    #[test]
+    fn covers_status() {
+        assert_eq!(parse_covers_status("Final"), Some(GameStatus::Final));
+        assert_eq!(parse_covers_status(" Final/OT "), Some(GameStatus::FinalOt));
+        assert_eq!(
+            parse_covers_status("3rd 5:32"),
+            Some(GameStatus::InProgress {
+                period: 3,
+                clock: "5:32".to_string()
+            })
+        );
+        assert_eq!(
+            parse_covers_status("Half"),
+            Some(GameStatus::InProgress {
+                period: 2,
+                clock: "Half".to_string()
+            })
+        );
+        assert_eq!(
+            parse_covers_status("2OT 1:05"),
+            Some(GameStatus::InProgress {
+                period: 6,
+                clock: "1:05".to_string()
+            })
+        );
+        assert_eq!(
+            parse_covers_status("Postponed"),
+            Some(GameStatus::Postponed)
+        );
+        assert_eq!(
+            parse_covers_status("8:00 PM ET"),
+            Some(GameStatus::Scheduled)
+        );
+        assert_eq!(parse_covers_status(""), None);
+    }
+
+    #[test]
+    fn playoff_series_from_fixture() {
+        let series = parse_playoff_series(&fixture("playoffs_NBA_2021.html")).unwrap();
+        assert_eq!(series.len(), 4);
+
+        let semifinal = &series[0];
+        assert_eq!(semifinal.round, 2);
+        assert_eq!(semifinal.team_1, "Milwaukee Bucks");
+        assert_eq!(semifinal.team_2, "Boston Celtics");
+        assert_eq!((semifinal.team_1_wins, semifinal.team_2_wins), (2, 1));
+
+        let first_round = &series[2];
+        assert_eq!(first_round.round, 1);
+        assert_eq!(first_round.team_1, "Boston Celtics");
+        assert_eq!(first_round.team_2, "Detroit Pistons");
+        assert_eq!((first_round.team_1_wins, first_round.team_2_wins), (4, 3));
+    }
+}
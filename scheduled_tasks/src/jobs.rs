@@ -0,0 +1,319 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use chrono_tz::Tz;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{PgPool, Postgres};
+use std::env;
+
+/// Everything the scheduled tasks do, JOBS decides when it's done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    ClosePolls,
+    RefreshViews,
+    LiveScores,
+    ScrapeSchedule,
+    WeeklyResults,
+    SendPolls,
+}
+
+impl Task {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ClosePolls => "close_polls",
+            Self::RefreshViews => "refresh_views",
+            Self::LiveScores => "live_scores",
+            Self::ScrapeSchedule => "scrape_schedule",
+            Self::WeeklyResults => "weekly_results",
+            Self::SendPolls => "send_polls",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        JOBS.iter()
+            .map(|job| job.task)
+            .find(|task| task.name() == name)
+    }
+
+    /// Tasks that have nothing to do once the season is over
+    pub fn in_season_only(&self) -> bool {
+        !matches!(self, Self::ClosePolls | Self::RefreshViews)
+    }
+}
+
+pub enum Schedule {
+    /// run whenever the given number of minutes passed since the last run
+    Every { minutes: i64 },
+    /// run every LIVE_SCRAPE_INTERVAL_MINUTES (2 by default) while games are being played
+    WhileGamesLive,
+    /// run once a day at the given local time
+    DailyAt {
+        hour: u32,
+        minute: u32,
+        time_zone: Tz,
+    },
+}
+
+pub struct Job {
+    pub task: Task,
+    pub schedule: Schedule,
+}
+
+/// The jobs are run in this order whenever they're due
+pub const JOBS: &[Job] = &[
+    Job {
+        task: Task::ClosePolls,
+        schedule: Schedule::Every { minutes: 5 },
+    },
+    Job {
+        task: Task::RefreshViews,
+        schedule: Schedule::Every { minutes: 15 },
+    },
+    Job {
+        task: Task::LiveScores,
+        schedule: Schedule::WhileGamesLive,
+    },
+    // last night's games are finished by now, so the weeks that ended yesterday are complete
+    Job {
+        task: Task::WeeklyResults,
+        schedule: Schedule::DailyAt {
+            hour: 6,
+            minute: 0,
+            time_zone: New_York,
+        },
+    },
+    Job {
+        task: Task::ScrapeSchedule,
+        schedule: Schedule::DailyAt {
+            hour: 6,
+            minute: 30,
+            time_zone: New_York,
+        },
+    },
+    Job {
+        task: Task::SendPolls,
+        schedule: Schedule::DailyAt {
+            hour: 14,
+            minute: 0,
+            time_zone: New_York,
+        },
+    },
+];
+
+impl Schedule {
+    /// A job is due if it never ran, or if it's supposed to have run since its last run.
+    /// Runs that were missed, e.g. because the scheduler was down, are caught up once.
+    /// games_live: if any game is being played right now
+    pub fn is_due(
+        &self,
+        last_run: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+        games_live: bool,
+    ) -> bool {
+        if let Self::WhileGamesLive = self {
+            if !games_live {
+                return false;
+            }
+        }
+
+        let last_run = match last_run {
+            Some(last_run) => last_run,
+            None => return true,
+        };
+
+        match self {
+            Self::Every { minutes } => now - last_run >= Duration::minutes(*minutes),
+            Self::WhileGamesLive => now - last_run >= live_scrape_interval(),
+            Self::DailyAt {
+                hour,
+                minute,
+                time_zone,
+            } => {
+                // the most recent time the job should have run, in the job's own time zone
+                // so it doesn't shift by an hour with daylight saving time
+                let local_now = now.with_timezone(time_zone);
+                let mut date = local_now.date().naive_local();
+                let time = chrono::NaiveTime::from_hms(*hour, *minute, 0);
+                if local_now.time() < time {
+                    date = date.pred();
+                }
+                let scheduled_run = time_zone
+                    .from_local_datetime(&date.and_time(time))
+                    .earliest()
+                    // the time doesn't exist on days the clocks are turned forward
+                    .unwrap_or_else(|| time_zone.from_utc_datetime(&date.and_time(time)));
+
+                last_run < scheduled_run.with_timezone(&Utc)
+            }
+        }
+    }
+}
+
+fn live_scrape_interval() -> Duration {
+    Duration::minutes(
+        env::var("LIVE_SCRAPE_INTERVAL_MINUTES")
+            .ok()
+            .and_then(|minutes| minutes.parse().ok())
+            .unwrap_or(2),
+    )
+}
+
+pub async fn get_last_run(pool: &PgPool, task: Task) -> anyhow::Result<Option<DateTime<Utc>>> {
+    Ok(
+        sqlx::query!("SELECT last_run FROM job_runs WHERE name = $1", task.name())
+            .fetch_optional(pool)
+            .await?
+            .map(|row| row.last_run),
+    )
+}
+
+pub async fn record_run(
+    pool: &PgPool,
+    task: Task,
+    started: DateTime<Utc>,
+    succeeded: bool,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO job_runs(name, last_run, succeeded) VALUES
+        ($1, $2, $3)
+        ON CONFLICT (name) DO
+            UPDATE SET (last_run, succeeded) = ($2, $3);
+        "#,
+        task.name(),
+        started,
+        succeeded
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Holds the advisory lock of a task, so a job run by hand and the same job run by the daemon
+/// or cron don't get in each other's way. The lock belongs to the connection, it's released
+/// with unlock_task or when the process dies.
+pub struct TaskLock {
+    connection: PoolConnection<Postgres>,
+    task: Task,
+}
+
+/// Takes the lock of the task, None if another process holds it
+pub async fn try_lock_task(pool: &PgPool, task: Task) -> anyhow::Result<Option<TaskLock>> {
+    let mut connection = pool.acquire().await?;
+    let locked = sqlx::query!(
+        "SELECT pg_try_advisory_lock(hashtext('scheduled_tasks ' || $1)) AS locked",
+        task.name()
+    )
+    .fetch_one(&mut connection)
+    .await?
+    .locked
+    .unwrap_or(false);
+
+    Ok(match locked {
+        true => Some(TaskLock { connection, task }),
+        false => None,
+    })
+}
+
+pub async fn unlock_task(lock: TaskLock) -> anyhow::Result<()> {
+    let TaskLock {
+        mut connection,
+        task,
+    } = lock;
+    sqlx::query!(
+        "SELECT pg_advisory_unlock(hashtext('scheduled_tasks ' || $1))",
+        task.name()
+    )
+    .fetch_one(&mut connection)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2021, month, day).and_hms(hour, minute, 0)
+    }
+
+    fn daily_at(hour: u32, minute: u32) -> Schedule {
+        Schedule::DailyAt {
+            hour,
+            minute,
+            time_zone: New_York,
+        }
+    }
+
+    #[test]
+    fn daily_job_runs_once_a_day() {
+        // 14:00 in New York is 19:00 UTC in the winter
+        let schedule = daily_at(14, 0);
+        let last_run = Some(utc(1, 10, 19, 0));
+
+        assert!(schedule.is_due(None, utc(1, 10, 12, 0), false));
+        assert!(!schedule.is_due(last_run, utc(1, 10, 23, 0), false));
+        assert!(!schedule.is_due(last_run, utc(1, 11, 18, 59), false));
+        assert!(schedule.is_due(last_run, utc(1, 11, 19, 0), false));
+    }
+
+    #[test]
+    fn missed_daily_run_is_caught_up_once() {
+        let schedule = daily_at(14, 0);
+        // the scheduler was down from the 8th to the morning of the 11th
+        let last_run = Some(utc(1, 8, 19, 0));
+        assert!(schedule.is_due(last_run, utc(1, 11, 12, 0), false));
+
+        // the missed runs are made up for with a single run, the next one is at the usual time
+        let last_run = Some(utc(1, 11, 12, 0));
+        assert!(!schedule.is_due(last_run, utc(1, 11, 13, 0), false));
+        assert!(schedule.is_due(last_run, utc(1, 11, 19, 0), false));
+    }
+
+    #[test]
+    fn daily_job_keeps_its_local_time_across_daylight_saving_time() {
+        // the clocks are turned forward on March 14 2021, 14:00 is 18:00 UTC from then on
+        let schedule = daily_at(14, 0);
+        let last_run = Some(utc(3, 13, 19, 0));
+        assert!(!schedule.is_due(last_run, utc(3, 14, 17, 59), false));
+        assert!(schedule.is_due(last_run, utc(3, 14, 18, 0), false));
+
+        // 02:30 doesn't exist on that day, the job runs anyway
+        let schedule = daily_at(2, 30);
+        let last_run = Some(utc(3, 13, 7, 30));
+        assert!(schedule.is_due(last_run, utc(3, 14, 12, 0), false));
+
+        // the clocks are turned back on November 7 2021, 01:30 happens twice but the job only runs
+        // at the first one
+        let schedule = daily_at(1, 30);
+        let last_run = Some(utc(11, 7, 5, 30));
+        assert!(!schedule.is_due(last_run, utc(11, 7, 6, 0), false));
+        assert!(!schedule.is_due(last_run, utc(11, 7, 6, 30), false));
+        assert!(schedule.is_due(last_run, utc(11, 8, 6, 30), false));
+    }
+
+    #[test]
+    fn every_job_waits_for_its_interval() {
+        let schedule = Schedule::Every { minutes: 5 };
+        let last_run = Some(utc(1, 10, 12, 0));
+
+        assert!(schedule.is_due(None, utc(1, 10, 12, 0), false));
+        assert!(!schedule.is_due(last_run, utc(1, 10, 12, 4), false));
+        assert!(schedule.is_due(last_run, utc(1, 10, 12, 5), false));
+        // missed runs are made up for with a single run as well
+        assert!(schedule.is_due(last_run, utc(1, 10, 13, 0), true));
+    }
+
+    #[test]
+    fn live_job_only_runs_while_games_are_live() {
+        let schedule = Schedule::WhileGamesLive;
+        let last_run = Some(utc(1, 10, 1, 0));
+
+        assert!(!schedule.is_due(None, utc(1, 10, 2, 0), false));
+        assert!(!schedule.is_due(last_run, utc(1, 10, 2, 0), false));
+        assert!(schedule.is_due(None, utc(1, 10, 2, 0), true));
+        assert!(schedule.is_due(last_run, utc(1, 10, 2, 0), true));
+        // the interval is a few minutes, not a whole hour
+        assert!(!schedule.is_due(Some(utc(1, 10, 2, 0)), utc(1, 10, 2, 0), true));
+    }
+}
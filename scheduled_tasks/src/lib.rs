@@ -0,0 +1,5 @@
+//! The jobs and scrapers of the scheduled tasks, main.rs runs them. They live in a library so the
+//! integration tests in tests/ can run the scrapers against a test database.
+pub mod data_source;
+pub mod jobs;
+pub mod scrape;
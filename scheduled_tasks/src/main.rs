@@ -1,125 +1,290 @@
-use basketball_betting_bot::Error;
-use std::collections::HashMap;
-mod scrape;
 use basketball_betting_bot::utils::*;
-use chrono::{Datelike, Timelike, Utc};
-use scrape::*;
+use basketball_betting_bot::{connect_pool, Error};
+use chrono::{Datelike, Utc};
+use scheduled_tasks::data_source::{self, DataSource};
+use scheduled_tasks::jobs::*;
+use scheduled_tasks::scrape::*;
 use sqlx::postgres::PgPool;
+use std::collections::HashMap;
 use std::env;
 use teloxide::prelude::*;
 
+/// scheduled_tasks
+///     runs every job that is due once, meant to be called by cron
+/// scheduled_tasks daemon
+///     keeps running and checks for due jobs every minute
+/// scheduled_tasks run <job>
+///     runs the job right away, whether it's due or not, unless it's running already
+/// scheduled_tasks new_season <year> <regular_season_start> <regular_season_end> <play_in_start> <playoffs_start> <playoffs_end>
+///     rolls the bot over to a new season, dates are given as YYYY-MM-DD
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let pool = PgPool::connect(
-        &env::var("DATABASE_URL").expect("Could not find environment variable DATABASE_URL"),
-    )
-    .await
-    .expect("Could not establish connection do database");
+    pretty_env_logger::init();
+
+    let pool = connect_pool().expect("Could not create the database pool");
 
     let bot = Bot::builder().build();
-    stop_poll(&pool, &bot).await?;
-    refresh_materialized_views(&pool).await?;
+    let source = data_source::from_env();
 
-    // do nothing if season is over
-    if east_coast_date_in_x_days(0, false)?
-        > chrono::NaiveDate::parse_from_str("2021-05-18", "%Y-%m-%d")?
-    {
-        return Ok(());
+    let args = env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("new_season") => new_season(&pool, &args[2..]).await,
+        Some("run") => {
+            let task = args
+                .get(2)
+                .map(String::as_str)
+                .and_then(Task::from_name)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "usage: scheduled_tasks run <job>\njobs: {}",
+                        JOBS.iter()
+                            .map(|job| job.task.name())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+            let lock = try_lock_task(&pool, task)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("job {} is running already", task.name()))?;
+            let result = run_task(task, &pool, &bot, source.as_ref()).await;
+            unlock_task(lock).await?;
+            result
+        }
+        Some("daemon") => loop {
+            // the daemon keeps going if the db is gone for a moment, the jobs are caught up later
+            if let Err(e) = run_due_jobs(&pool, &bot, source.as_ref()).await {
+                log::error!("Could not run jobs: {}", e);
+            }
+            tokio::time::delay_for(std::time::Duration::from_secs(60)).await;
+        },
+        _ => run_due_jobs(&pool, &bot, source.as_ref()).await,
     }
+}
 
-    match Utc::now().hour() {
-        0..=4 | 7..=9 | 20..=23 => scrape_games_live(&pool).await.unwrap(),
-        5..=6 => {
-            cache_games(
-                get_games(
-                    &pool,
-                    10,
-                    east_coast_date_in_x_days(1, false)?,
-                    east_coast_date_in_x_days(7, false)?,
-                )
-                .await
-                .unwrap_or_default(),
-            )
-            .unwrap_or_else(|error| {
-                dbg!("Can't cache games!", error);
-            });
+async fn run_due_jobs(pool: &PgPool, bot: &Bot, source: &dyn DataSource) -> anyhow::Result<()> {
+    let games_live = games_in_progress(pool).await?;
 
-            scrape_games_live(&pool).await.unwrap();
+    for job in JOBS {
+        // the job is skipped while another process runs it, the lock is taken before checking
+        // the last run so a run that just finished isn't repeated
+        // a job that fails doesn't keep the jobs after it from running
+        let lock = match try_lock_task(pool, job.task).await {
+            Ok(Some(lock)) => lock,
+            Ok(None) => {
+                log::info!("Job {} is running already", job.task.name());
+                continue;
+            }
+            Err(e) => {
+                log::error!("Could not lock job {}: {}", job.task.name(), e);
+                continue;
+            }
+        };
+        // the lock stays with the connection until it's unlocked, even if the job failed
+        if let Err(e) = run_job_if_due(job, pool, bot, source, games_live).await {
+            log::error!("Could not run job {}: {}", job.task.name(), e);
+        }
+        if let Err(e) = unlock_task(lock).await {
+            log::error!("Could not unlock job {}: {}", job.task.name(), e);
         }
+    }
+
+    Ok(())
+}
+
+async fn run_job_if_due(
+    job: &Job,
+    pool: &PgPool,
+    bot: &Bot,
+    source: &dyn DataSource,
+    games_live: bool,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let last_run = get_last_run(pool, job.task).await?;
+    if !job.schedule.is_due(last_run, now, games_live) {
+        return Ok(());
+    }
+
+    log::info!("Running job {}", job.task.name());
+    let result = run_task(job.task, pool, bot, source).await;
+    if let Err(e) = &result {
+        log::error!("Job {} failed: {}", job.task.name(), e);
+    }
+
+    // failed runs count as runs as well, otherwise a broken scraper would be retried every minute
+    record_run(pool, job.task, now, result.is_ok()).await?;
+
+    Ok(())
+}
+
+async fn run_task(
+    task: Task,
+    pool: &PgPool,
+    bot: &Bot,
+    source: &dyn DataSource,
+) -> anyhow::Result<()> {
+    let season = get_active_season(pool).await?;
 
-        10..=11 => {
+    let today = east_coast_date_in_x_days(0, false)?;
+
+    // do nothing if season is over, the bracket challenge keeps going until the finals are over
+    if task.in_season_only() && today > season.playoffs_over_date() {
+        return Ok(());
+    }
+
+    match task {
+        Task::ClosePolls => {
+            stop_poll(pool, bot).await?;
+            stop_series_polls(pool, bot).await?;
+        }
+        Task::RefreshViews => refresh_materialized_views(pool).await?,
+        Task::LiveScores => scrape_games_live(pool, bot, source).await?,
+        Task::WeeklyResults => broadcast_weekly_results(pool, bot).await?,
+        Task::ScrapeSchedule => {
             let scraped_months = get_relevant_months();
-            dbg!(&scraped_months);
-            scrape_teams().await?;
+            scrape_teams(pool, source, season.year).await?;
             for month in scraped_months {
-                scrape_games(month).await?;
+                scrape_games(pool, bot, source, season.year, month).await?;
             }
-            cache_games(
-                get_games(
-                    &pool,
-                    10,
-                    east_coast_date_in_x_days(1, false)?,
-                    east_coast_date_in_x_days(7, false)?,
-                )
+            // the live scores page has the lines of the upcoming games
+            scrape_games_live(pool, bot, source).await?;
+            if today >= season.play_in_start {
+                scrape_playoff_series(pool, source, season.id, season.year).await?;
+            }
+            let (start_date, end_date) = season.upcoming_bet_week()?;
+            // an empty cache would be served as if the week had no games
+            if let Ok(games) =
+                get_games_for_mode(pool, GameMode::default(), start_date, end_date).await
+            {
+                cache_games(&games, GameMode::default(), start_date, end_date)
+                    .await
+                    .unwrap_or_else(|error| {
+                        log::warn!("Can't cache games: {}", error);
+                    });
+            }
+        }
+        Task::SendPolls => send_weekly_polls(pool, bot, &season).await?,
+    }
+
+    Ok(())
+}
+
+async fn send_weekly_polls(pool: &PgPool, bot: &Bot, season: &Season) -> anyhow::Result<()> {
+    if !active_chats_exist(pool).await? {
+        return Ok(());
+    }
+
+    let chats = sqlx::query!("SELECT DISTINCT id FROM chats WHERE is_active = True")
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+
+    let today = east_coast_date_in_x_days(0, false)?;
+
+    // send message if the regular season is over for the first time
+    if today == season.season_over_date() {
+        for chat_id in &chats {
+            let ranking_system = get_ranking_system(pool, chat_id.id)
                 .await
-                .unwrap_or_default(),
-            )
-            .unwrap_or_else(|error| {
-                dbg!("Can't cache games!", error);
-            });
+                .unwrap_or_default();
+            let mut message = format!(
+                "The NBA regular season is over! Check out the results {}\nFrom now on you get polls for the play-in and playoff games every day.",
+                ranking_system.command()
+            );
+            if get_bracket_challenge(pool, chat_id.id).await? {
+                message.push_str(
+                    "\nThe bracket challenge starts as well: you get a poll for every playoff series!",
+                );
+            }
+            bot.send_message(chat_id.id, message).send().await?;
         }
+    }
 
-        12..=17 => {}
-        18..=19 => {
-            if active_chats_exist(&pool).await? {
-                let chats = sqlx::query!("SELECT DISTINCT id FROM chats WHERE is_active = True")
-                    .fetch_all(&pool)
-                    .await
-                    .unwrap_or_default();
-
-                // send message if season is over for the first time
-                if east_coast_date_in_x_days(0, false)?
-                    == chrono::NaiveDate::parse_from_str("2021-05-17", "%Y-%m-%d")?
-                    && Utc::now().minute() < 30
-                {
-                    for chat_id in chats {
-                        {
-                            bot.send_message(chat_id.id, "Your NBA betting season is over! Check out the results /full_standings")
-                                .send()
-                                .await?;
-                        }
-                    }
-                    return Ok(());
-                }
-
-                let mut games = cache_to_games().unwrap_or_default();
-                if games.len() < 11 {
-                    games = get_games(
-                        &pool,
-                        10,
-                        east_coast_date_in_x_days(1, false)?,
-                        east_coast_date_in_x_days(7, false)?,
-                    )
-                    .await
-                    .unwrap_or_default();
-                }
-
-                for chat_id in chats {
-                    let poll_sent_success = send_polls(&pool, chat_id.id, &bot, &games).await;
-
-                    if let Err(e) = poll_sent_success {
-                        eprintln!(
-                            "ERROR {e}\nCould not send polls for chat_id {chat_id}",
-                            e = e,
-                            chat_id = chat_id.id
-                        );
-                    }
-                }
+    // send message if season is over for the first time
+    if today == season.playoffs_over_date() {
+        for chat_id in chats {
+            let ranking_system = get_ranking_system(pool, chat_id.id)
+                .await
+                .unwrap_or_default();
+            let mut message = format!(
+                "Your NBA betting season is over! Check out the results {}",
+                ranking_system.command()
+            );
+            if get_bracket_challenge(pool, chat_id.id).await? {
+                message.push_str("\nThe final bracket is waiting for you: /bracket_standings");
             }
+            bot.send_message(chat_id.id, message).send().await?;
         }
-        _ => {}
+        return Ok(());
     }
 
+    // chats playing the bracket challenge get a poll for every series once the regular season is over
+    if today >= season.season_over_date() {
+        for chat_id in &chats {
+            if !get_bracket_challenge(pool, chat_id.id).await? {
+                continue;
+            }
+            if let Err(e) = send_series_polls(pool, chat_id.id, bot).await {
+                log::error!(
+                    "Could not send series polls for chat_id {}: {}",
+                    chat_id.id,
+                    e
+                );
+            }
+        }
+    }
+
+    let (start_date, end_date) = season.upcoming_bet_week()?;
+
+    // chats playing the same game mode get the same games, so we only query them once
+    let mut games_per_mode: HashMap<GameMode, Vec<Game>> = HashMap::new();
+
+    for chat_id in chats {
+        let game_mode = get_game_mode(pool, chat_id.id).await.unwrap_or_default();
+        if !games_per_mode.contains_key(&game_mode) {
+            let games = match cache_to_games(game_mode, start_date, end_date).await {
+                Ok(games) => games,
+                Err(_) => get_games_for_mode(pool, game_mode, start_date, end_date)
+                    .await
+                    .unwrap_or_default(),
+            };
+            games_per_mode.insert(game_mode, games);
+        }
+        let games = &games_per_mode[&game_mode];
+        let games = include_pinned_teams(pool, chat_id.id, games, start_date, end_date)
+            .await
+            .unwrap_or_else(|_| games.to_vec());
+
+        let poll_sent_success = send_polls(pool, chat_id.id, bot, &games).await;
+
+        if let Err(e) = poll_sent_success {
+            log::error!("Could not send polls for chat_id {}: {}", chat_id.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn new_season(pool: &PgPool, args: &[String]) -> anyhow::Result<()> {
+    if args.len() != 6 {
+        anyhow::bail!(
+            "usage: scheduled_tasks new_season <year> <regular_season_start> <regular_season_end> <play_in_start> <playoffs_start> <playoffs_end>"
+        );
+    }
+    let date = |i: usize| chrono::NaiveDate::parse_from_str(&args[i], "%Y-%m-%d");
+
+    let season = Season {
+        id: -1,
+        year: args[0].parse()?,
+        regular_season_start: date(1)?,
+        regular_season_end: date(2)?,
+        play_in_start: date(3)?,
+        playoffs_start: date(4)?,
+        playoffs_end: date(5)?,
+    };
+
+    start_new_season(pool, &season).await?;
+    log::info!("Started season {}", season.year);
+
     Ok(())
 }
 
@@ -1,89 +1,43 @@
-use basketball_betting_bot::utils::set_last_updated;
+use crate::data_source::DataSource;
+use basketball_betting_bot::utils::{handle_rescheduled_game, set_last_updated, GameStatus};
 use basketball_betting_bot::Error;
-use chrono::{DateTime, FixedOffset};
-use log::warn;
-use scraper::{Html, Selector};
-use select::document::Document;
-use select::predicate::Class;
+use chrono::{DateTime, FixedOffset, Utc};
 use sqlx::postgres::PgPool;
-use std::collections::HashMap;
-use std::env;
-use std::fs::File;
-use std::io::prelude::*;
+use teloxide::Bot;
 
-pub async fn scrape_teams() -> Result<(), Error> {
-    let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
-    // in the new year we can use the standings of the actual season, when enough games are played,
-    // such thtat the srs metric has significant meaning
-    //let year = chrono::Utc::now().year();
-    let year = 2021;
-    let link = format!(
-        "https://www.basketball-reference.com/leagues/NBA_{year}.html",
-        year = year
-    );
-    let resp = reqwest::get(&link).await?.text().await?;
-    let doc = Html::parse_document(&resp);
-    let selector = Selector::parse("tr").unwrap();
-
-    for entry in doc.select(&selector) {
-        let td = entry.text().collect::<Vec<_>>();
-
-        // if the row has 8 or 9 entries, it's a team description
-        if vec![8, 9, 10].contains(&td.len()) {
-            let name = td[0];
-            let srs = td[td.len() - 1].parse::<sqlx::types::BigDecimal>().unwrap();
-            let wins = td[td.len() - 7].parse::<i32>().unwrap();
-            let losses = td[td.len() - 6].parse::<i32>().unwrap();
-            sqlx::query!(
-                r#"
+/// year: the year in which the season ends, e.g. 2021 for the 2020-21 season
+pub async fn scrape_teams(pool: &PgPool, source: &dyn DataSource, year: i32) -> anyhow::Result<()> {
+    for team in source.team_standings(year).await? {
+        sqlx::query!(
+            r#"
                 INSERT INTO teams(name,wins,losses,srs) VALUES
                 ($1, $2, $3, $4)
                 ON CONFLICT (name) DO
                 UPDATE SET (wins, losses, srs) = ($2, $3, $4);
             "#,
-                name,
-                wins,
-                losses,
-                srs
-            )
-            .execute(&pool)
-            .await
-            .unwrap_or_default();
-        }
+            team.name,
+            team.wins,
+            team.losses,
+            team.srs
+        )
+        .execute(pool)
+        .await
+        .unwrap_or_default();
     }
 
     Ok(())
 }
-pub async fn scrape_games(month: String) -> Result<(), Error> {
-    let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
-    let link = format!(
-        "https://www.basketball-reference.com/leagues/NBA_2021_games-{month}.html",
-        month = month
-    )
-    .to_string();
-    let resp = reqwest::get(&link).await?.text().await?;
-    let doc = Html::parse_document(&resp);
-    let selector = Selector::parse("tr").unwrap();
-    for entry in doc.select(&selector) {
-        let td = entry.text().collect::<Vec<_>>();
-        let date = td[0];
-
-        let time = td[1].replace("0p", "0:00 pm -0500");
-        let dtg = format!("{} {}", date, time);
-        let dt = DateTime::parse_from_str(&dtg, "%a, %b %d, %Y %I:%M:%S %P %z");
-
-        // if we can't parse the date it almost certainly means the row is a header and not a game
-        let mut skip = false;
-        if let Err(e) = dt {
-            skip = true;
-            warn!("skipped the row with error {}", e);
-        }
-        if skip {
-            continue;
-        }
 
+pub async fn scrape_games(
+    pool: &PgPool,
+    bot: &Bot,
+    source: &dyn DataSource,
+    year: i32,
+    month: String,
+) -> anyhow::Result<()> {
+    for game in source.schedule(year, &month).await? {
         // if game is more than 9 days away we don't want to scrape it
-        if dt?
+        if game.date
             > chrono::Utc::now()
                 .checked_add_signed(chrono::Duration::days(9))
                 .unwrap()
@@ -91,208 +45,187 @@ pub async fn scrape_games(month: String) -> Result<(), Error> {
             continue;
         }
 
-        let away_team = td[2].to_string();
-
-        // if row has 4 entries, the game was not played yet
-        match td.len() {
-            4 => {
-                let home_team = td[3].to_string();
-
-                let game = Game {
-                    date: dt?,
-                    away_team,
-                    home_team,
-                };
-
-                let away_team_id = get_team_id(&pool, game.away_team).await?;
-                let home_team_id = get_team_id(&pool, game.home_team).await?;
+        // e.g. an All-Star game or a renamed team, the other games are scraped anyway
+        let (away_team_id, home_team_id) =
+            match get_team_ids(pool, &game.away_team, &game.home_team).await? {
+                Some(team_ids) => team_ids,
+                None => continue,
+            };
 
-                add_game(&pool, game.date, away_team_id, 0, home_team_id, 0).await?;
-            }
-            _ => {
-                let home_team = td[4].to_string();
-                let home_points: i32 = td[5].parse().unwrap_or(0);
-                let away_points: i32 = td[3].parse().unwrap_or(0);
-
-                let game = FinishedGame {
-                    date: dt?,
-                    away_team,
-                    away_points,
-                    home_team,
-                    home_points,
-                };
-                let away_team_id = get_team_id(&pool, game.away_team).await?;
-                let home_team_id = get_team_id(&pool, game.home_team).await?;
-
-                add_game(
-                    &pool,
-                    game.date,
-                    away_team_id,
-                    game.away_points,
-                    home_team_id,
-                    game.home_points,
-                )
-                .await?;
+        if game.status != Some(GameStatus::Postponed) {
+            if let Some((game_id, old_date_time)) =
+                move_rescheduled_game(pool, game.date, away_team_id, home_team_id).await?
+            {
+                handle_rescheduled_game(pool, bot, game_id, old_date_time).await?;
             }
         }
+
+        add_game(
+            pool,
+            game.date,
+            away_team_id,
+            game.away_points,
+            home_team_id,
+            game.home_points,
+            game.status.as_ref(),
+        )
+        .await?;
     }
     Ok(())
 }
 
-pub async fn scrape_games_live(pool: &PgPool) -> anyhow::Result<()> {
-    let link = String::from("https://www.covers.com/sports/nba/matchups");
-    let full_names = [
-        "Atlanta Hawks".to_string(),
-        "Boston Celtics".to_string(),
-        "Brooklyn Nets".to_string(),
-        "Charlotte Hornets".to_string(),
-        "Chicago Bulls".to_string(),
-        "Cleveland Cavaliers".to_string(),
-        "Dallas Mavericks".to_string(),
-        "Denver Nuggets".to_string(),
-        "Detroit Pistons".to_string(),
-        "Golden State Warriors".to_string(),
-        "Houston Rockets".to_string(),
-        "Indiana Pacers".to_string(),
-        "Los Angeles Clippers".to_string(),
-        "Los Angeles Lakers".to_string(),
-        "Memphis Grizzlies".to_string(),
-        "Miami Heat".to_string(),
-        "Milwaukee Bucks".to_string(),
-        "Minnesota Timberwolves".to_string(),
-        "New Orleans Pelicans".to_string(),
-        "New York Knicks".to_string(),
-        "Oklahoma City Thunder".to_string(),
-        "Orlando Magic".to_string(),
-        "Philadelphia 76ers".to_string(),
-        "Phoenix Suns".to_string(),
-        "Portland Trail Blazers".to_string(),
-        "Sacramento Kings".to_string(),
-        "San Antonio Spurs".to_string(),
-        "Toronto Raptors".to_string(),
-        "Utah Jazz".to_string(),
-        "Washington Wizards".to_string(),
-    ];
-
-    let short_names = [
-        "ATL".to_string(),
-        "BOS".to_string(),
-        "BK".to_string(),
-        "CHA".to_string(),
-        "CHI".to_string(),
-        "CLE".to_string(),
-        "DAL".to_string(),
-        "DEN".to_string(),
-        "DET".to_string(),
-        "GS".to_string(),
-        "HOU".to_string(),
-        "IND".to_string(),
-        "LAC".to_string(),
-        "LAL".to_string(),
-        "MEM".to_string(),
-        "MIA".to_string(),
-        "MIL".to_string(),
-        "MIN".to_string(),
-        "NO".to_string(),
-        "NY".to_string(),
-        "OKC".to_string(),
-        "ORL".to_string(),
-        "PHI".to_string(),
-        "PHO".to_string(),
-        "POR".to_string(),
-        "SAC".to_string(),
-        "SA".to_string(),
-        "TOR".to_string(),
-        "UTA".to_string(),
-        "WAS".to_string(),
-    ];
-    let short_name_to_full_name: HashMap<_, _> =
-        short_names.iter().zip(full_names.iter()).collect();
-    //let resp = reqwest::blocking::get(&link).unwrap();
-
-    // Document::document::from_read expects type that implements read::io trait
-    // In blocking version of reqwest, the Response type implement that trait but in the
-    // async version, Response does not. To still be able to use this function without
-    // creating a different blocking runtime with reqwest, which does not work anyway,
-    // I surrendered and write the reqwest response to file and read from it immediately
-    // Sigh...
-    let resp = reqwest::get(&link).await?.text().await?;
-    let mut file = File::create("scrape.html")?;
-    file.write_all(resp.as_bytes()).unwrap();
-    let path = std::path::Path::new("scrape.html");
-    let file = File::open(path).unwrap();
-    let document = Document::from_read(file).unwrap();
+pub async fn scrape_games_live(
+    pool: &PgPool,
+    bot: &Bot,
+    source: &dyn DataSource,
+) -> anyhow::Result<()> {
+    for game in source.live_scores().await? {
+        // e.g. an All-Star game or a renamed team, the other games are scraped anyway
+        let (away_team_id, home_team_id) =
+            match get_team_ids(pool, &game.away_team, &game.home_team).await? {
+                Some(team_ids) => team_ids,
+                None => continue,
+            };
 
-    for node in document.find(Class("cmg_game_data")) {
-        let home_points = node.attr("data-home-score");
-        if home_points.is_none() {
-            continue;
+        if game.status != Some(GameStatus::Postponed) {
+            if let Some((game_id, old_date_time)) =
+                move_rescheduled_game(pool, game.date, away_team_id, home_team_id).await?
+            {
+                handle_rescheduled_game(pool, bot, game_id, old_date_time).await?;
+            }
         }
-        let home_points = home_points.unwrap_or("0").parse::<i32>()?;
-        let away_points = node.attr("data-away-score").unwrap_or("0").parse::<i32>()?;
-        //2021-01-05T22:47:51.0000000
-        let last_updated = chrono::DateTime::parse_from_rfc3339(&format!(
-            "{date}{offset}",
-            date = node
-                .attr("data-last-update")
-                .unwrap_or("2000-01-01T00:00:00"),
-            offset = "-04:00"
-        ))?;
-        let game_date = chrono::DateTime::parse_from_str(
-            &format!(
-                "{date}{offset}",
-                date = node.attr("data-game-date").unwrap_or("2000-01-01 00:00:00"),
-                offset = "-0500"
-            ),
-            "%Y-%m-%d %H:%M:%S %z",
-        )
-        .unwrap();
-        let home_team_short = node
-            .attr("data-home-team-shortname-search")
-            .unwrap()
-            .to_string();
-        let away_team_short = node
-            .attr("data-away-team-shortname-search")
-            .unwrap()
-            .to_string();
-
-        dbg!(
-            &home_points,
-            &away_points,
-            &last_updated,
-            &game_date,
-            &home_team_short,
-            short_name_to_full_name[&home_team_short],
-            &away_team_short,
-            short_name_to_full_name[&away_team_short]
-        );
-
-        let game = FinishedGame {
-            date: game_date,
-            away_team: short_name_to_full_name[&away_team_short].to_owned(),
-            away_points,
-            home_team: short_name_to_full_name[&home_team_short].to_owned(),
-            home_points,
-        };
-        let away_team_id = get_team_id(&pool, game.away_team).await?;
-        let home_team_id = get_team_id(&pool, game.home_team).await?;
 
         add_game(
-            &pool,
+            pool,
             game.date,
             away_team_id,
             game.away_points,
             home_team_id,
             game.home_points,
+            game.status.as_ref(),
         )
         .await?;
 
-        set_last_updated(last_updated)?;
+        // the lines are closed once the game started
+        if game.home_spread.is_some() || game.total.is_some() {
+            sqlx::query!(
+                r#"
+                UPDATE games SET (home_spread, total) = (COALESCE($1, home_spread), COALESCE($2, total))
+                WHERE date_time = $3 AND away_team = $4 AND home_team = $5 AND status = 'scheduled'
+                "#,
+                game.home_spread,
+                game.total,
+                game.date,
+                away_team_id,
+                home_team_id
+            )
+            .execute(pool)
+            .await?;
+        }
+
+        // the update time is only shown next to the standings, no reason to stop the scrape
+        if let Err(e) = set_last_updated(game.last_updated).await {
+            log::warn!("Could not store the last update in Redis: {}", e);
+        }
     }
 
     Ok(())
 }
 
+/// season_id: the series are stored for this season, year: the year in which it ends
+pub async fn scrape_playoff_series(
+    pool: &PgPool,
+    source: &dyn DataSource,
+    season_id: i32,
+    year: i32,
+) -> anyhow::Result<()> {
+    for series in source.playoff_series(year).await? {
+        let (team_1_id, team_2_id) =
+            match get_team_ids(pool, &series.team_1, &series.team_2).await? {
+                Some(team_ids) => team_ids,
+                None => continue,
+            };
+
+        // the team with the lower id is always team_1, so every series is only stored once
+        let (team_1, team_1_wins, team_2, team_2_wins) = match team_1_id < team_2_id {
+            true => (team_1_id, series.team_1_wins, team_2_id, series.team_2_wins),
+            false => (team_2_id, series.team_2_wins, team_1_id, series.team_1_wins),
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO playoff_series(season_id, round, team_1, team_2, team_1_wins, team_2_wins) VALUES
+            ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (season_id, round, team_1, team_2) DO
+                UPDATE SET (team_1_wins, team_2_wins) = ($5, $6);
+            "#,
+            season_id,
+            series.round,
+            team_1,
+            team_2,
+            team_1_wins,
+            team_2_wins
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// A rescheduled game shows up at its new date as if it was a new game. Instead of adding a second
+/// game, the postponed game of the same matchup this season is moved, so its polls and bets move
+/// with it. Returns the id and the old date of the moved game.
+async fn move_rescheduled_game(
+    pool: &PgPool,
+    date_time: DateTime<FixedOffset>,
+    away_team_id: i32,
+    home_team_id: i32,
+) -> Result<Option<(i32, DateTime<Utc>)>, Error> {
+    // only games marked as postponed are moved. A game that was played but whose score wasn't
+    // scraped yet or a tip-off time the sources disagree on must not take over another game.
+    let rescheduled_game = sqlx::query!(
+        r#"
+        SELECT games.id, games.date_time
+        FROM games, seasons
+        WHERE seasons.is_active = True
+        AND (games.date_time AT TIME ZONE 'America/New_York')::date
+            BETWEEN seasons.regular_season_start AND seasons.playoffs_end
+        AND games.away_team = $2
+        AND games.home_team = $3
+        AND games.date_time <> $1
+        AND games.status = 'postponed'
+        AND NOT EXISTS(
+            SELECT * FROM games WHERE date_time = $1 AND away_team = $2 AND home_team = $3
+        )
+        ORDER BY games.date_time ASC
+        LIMIT 1
+        "#,
+        date_time,
+        away_team_id,
+        home_team_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let rescheduled_game = match rescheduled_game {
+        Some(game) => game,
+        None => return Ok(None),
+    };
+
+    sqlx::query!(
+        "UPDATE games SET (date_time, status) = ($1, 'scheduled') WHERE id = $2",
+        date_time,
+        rescheduled_game.id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(rescheduled_game
+        .date_time
+        .map(|old_date_time| (rescheduled_game.id, old_date_time)))
+}
+
 async fn add_game(
     pool: &PgPool,
     date_time: DateTime<FixedOffset>,
@@ -300,20 +233,38 @@ async fn add_game(
     away_points: i32,
     home_team_id: i32,
     home_points: i32,
+    status: Option<&GameStatus>,
 ) -> Result<(), Error> {
+    // period and clock are only known while the game is in progress
+    let (period, clock) = match status {
+        Some(GameStatus::InProgress { period, clock }) => (Some(*period), Some(clock.to_owned())),
+        _ => (None, None),
+    };
+
+    // without a status we keep the one that's already stored
     sqlx::query!(
         r#"
-        INSERT INTO games(date_time, away_team, away_points, home_team, home_points)
+        INSERT INTO games(date_time, away_team, away_points, home_team, home_points, status, period, clock)
         VALUES
-        ($1, $2, $3, $4, $5)
+        ($1, $2, $3, $4, $5, COALESCE($6, 'scheduled'), $7, $8)
         ON CONFLICT (date_time, away_team, home_team) DO
-            UPDATE SET (date_time, away_points, home_points) = ($1, $3, $5);
+            UPDATE SET (date_time, away_points, home_points, status, period, clock) = (
+                $1,
+                $3,
+                $5,
+                COALESCE($6, games.status),
+                CASE WHEN $6 IS NULL THEN games.period ELSE $7 END,
+                CASE WHEN $6 IS NULL THEN games.clock ELSE $8 END
+            );
         "#,
         date_time,
         away_team_id,
         away_points,
         home_team_id,
-        home_points
+        home_points,
+        status.map(GameStatus::as_str),
+        period,
+        clock
     )
     .execute(pool)
     .await?;
@@ -321,7 +272,7 @@ async fn add_game(
     Ok(())
 }
 
-async fn get_team_id(pool: &PgPool, team_name: String) -> Result<i32, Error> {
+async fn get_team_id(pool: &PgPool, team_name: &str) -> Result<Option<i32>, Error> {
     Ok(sqlx::query!(
         r#"
             SELECT id FROM teams WHERE name = $1"#,
@@ -329,28 +280,28 @@ async fn get_team_id(pool: &PgPool, team_name: String) -> Result<i32, Error> {
     )
     .fetch_optional(pool)
     .await?
-    .unwrap()
-    .id)
-}
-#[derive(Debug, Clone)]
-struct Game {
-    date: DateTime<FixedOffset>,
-    //date: time::OffsetDateTime,
-    away_team: String,
-    home_team: String,
+    .map(|row| row.id))
 }
 
-#[derive(Debug, Clone)]
-struct FinishedGame {
-    date: DateTime<FixedOffset>,
-    away_team: String,
-    away_points: i32,
-    home_team: String,
-    home_points: i32,
-}
-
-#[derive(Debug)]
-struct Team {
-    name: String,
-    srs: f32,
+/// The ids of both teams, None if one of them isn't in the teams table
+async fn get_team_ids(
+    pool: &PgPool,
+    team_1: &str,
+    team_2: &str,
+) -> Result<Option<(i32, i32)>, Error> {
+    let team_ids = (
+        get_team_id(pool, team_1).await?,
+        get_team_id(pool, team_2).await?,
+    );
+    match team_ids {
+        (Some(team_1_id), Some(team_2_id)) => Ok(Some((team_1_id, team_2_id))),
+        _ => {
+            log::warn!(
+                "skipping {} vs {}, at least one of the teams is unknown",
+                team_1,
+                team_2
+            );
+            Ok(None)
+        }
+    }
 }
@@ -0,0 +1,112 @@
+//! Runs the scrapers with the saved pages in fixtures/ and checks what ends up in the database.
+//! The tests empty the teams and games tables, so they need a database of their own:
+//!
+//!     TEST_DATABASE_URL=postgres://localhost/betting_bot_test cargo test -- --ignored
+
+use scheduled_tasks::data_source::FixtureSource;
+use scheduled_tasks::scrape::{scrape_games, scrape_games_live, scrape_teams};
+use sqlx::postgres::PgPool;
+use teloxide::Bot;
+
+async fn test_pool() -> PgPool {
+    let pool = PgPool::connect(
+        &std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL has to be set"),
+    )
+    .await
+    .unwrap();
+    sqlx::migrate!("../migrations").run(&pool).await.unwrap();
+    sqlx::query("TRUNCATE teams, games RESTART IDENTITY CASCADE")
+        .execute(&pool)
+        .await
+        .unwrap();
+    pool
+}
+
+/// (away team, home team, away points, home points, status) of every game, ordered by tip-off
+async fn stored_games(pool: &PgPool) -> Vec<(String, String, i32, i32, String)> {
+    sqlx::query_as(
+        r#"
+        SELECT away.name, home.name, games.away_points, games.home_points, games.status
+        FROM games
+        JOIN teams AS away ON away.id = games.away_team
+        JOIN teams AS home ON home.id = games.home_team
+        ORDER BY games.date_time, games.id
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+fn game(
+    away_team: &str,
+    home_team: &str,
+    away_points: i32,
+    home_points: i32,
+    status: &str,
+) -> (String, String, i32, i32, String) {
+    (
+        away_team.to_string(),
+        home_team.to_string(),
+        away_points,
+        home_points,
+        status.to_string(),
+    )
+}
+
+#[tokio::test]
+#[ignore]
+async fn scrape_fixtures_into_db() {
+    let pool = test_pool().await;
+    let source = FixtureSource::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"));
+    // only used to announce rescheduled games, there are none in the fixtures
+    let bot = Bot::new("0:fixtures");
+
+    scrape_teams(&pool, &source, 2021).await.unwrap();
+    let teams: Vec<(String, i32, i32)> =
+        sqlx::query_as("SELECT name, wins, losses FROM teams ORDER BY name")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(teams.len(), 7);
+    assert!(teams.contains(&("Utah Jazz".to_string(), 52, 20)));
+
+    scrape_games(&pool, &bot, &source, 2021, "january".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        stored_games(&pool).await,
+        vec![
+            game("Milwaukee Bucks", "Boston Celtics", 121, 119, "final"),
+            game("Houston Rockets", "Utah Jazz", 94, 112, "final"),
+            game("Miami Heat", "Boston Celtics", 0, 0, "scheduled"),
+            game("Detroit Pistons", "Los Angeles Lakers", 0, 0, "scheduled"),
+        ]
+    );
+
+    // the live scores update the games of the schedule, Chicago isn't in the standings fixture
+    // so its game is skipped
+    scrape_games_live(&pool, &bot, &source).await.unwrap();
+    assert_eq!(
+        stored_games(&pool).await,
+        vec![
+            game("Milwaukee Bucks", "Boston Celtics", 121, 119, "final"),
+            game("Houston Rockets", "Utah Jazz", 94, 112, "final"),
+            game("Miami Heat", "Boston Celtics", 104, 110, "final"),
+            game(
+                "Detroit Pistons",
+                "Los Angeles Lakers",
+                15,
+                12,
+                "in_progress"
+            ),
+        ]
+    );
+
+    // scraping the same pages again doesn't add any games
+    scrape_games(&pool, &bot, &source, 2021, "january".to_string())
+        .await
+        .unwrap();
+    scrape_games_live(&pool, &bot, &source).await.unwrap();
+    assert_eq!(stored_games(&pool).await.len(), 4);
+}
@@ -0,0 +1,289 @@
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use teloxide::utils::command::BotCommand;
+
+/// Every command the bot understands. The /help text and the command list of bot_info.txt are
+/// generated from the descriptions, run the bot with `commands` to print the list for BotFather.
+#[derive(BotCommand, Debug, PartialEq)]
+#[command(
+    rename = "lowercase",
+    description = "These are the commands BasketballBettingBot understands:"
+)]
+pub enum Command {
+    #[command(
+        description = "Start your season! (wait until everyone who wants to participate was added to the group)"
+    )]
+    Start,
+    #[command(description = "Show standings of your official ranking system")]
+    Standings,
+    #[command(
+        rename = "full_standings",
+        description = "Show standings for the whole season"
+    )]
+    FullStandings,
+    #[command(
+        rename = "all_bets",
+        description = "Show fraction of correct bets for the whole season (Alternative to weekly standings)"
+    )]
+    AllBets,
+    #[command(
+        rename = "week_standings",
+        description = "Show standings for a specified week, e.g. /week_standings 4 or /week_standings last"
+    )]
+    WeekStandings(String),
+    #[command(
+        description = "Rank your picks of the week from most to least confident, e.g. /confidence 3 1 2 (confidence ranking only)"
+    )]
+    Confidence(String),
+    #[command(
+        rename = "confidence_standings",
+        description = "Show confidence points for the whole season"
+    )]
+    ConfidenceStandings,
+    #[command(
+        rename = "upset_standings",
+        description = "Show points with upset bonus for the whole season"
+    )]
+    UpsetStandings,
+    #[command(
+        rename = "game_results",
+        description = "Show game results for ongoing week or a specified one, e.g. /game_results last"
+    )]
+    GameResults(String),
+    #[command(description = "Show or change which games are sent each week (admins only)")]
+    Mode(String),
+    #[command(description = "Show or change the official ranking system (admins only)")]
+    Ranking(String),
+    #[command(description = "Show or change the time zone of game times (admins only)")]
+    Timezone(String),
+    #[command(
+        rename = "pin_team",
+        description = "Always include a team in your bets (admins only)"
+    )]
+    PinTeam(String),
+    #[command(
+        rename = "unpin_team",
+        description = "Stop always including a team in your bets (admins only)"
+    )]
+    UnpinTeam(String),
+    #[command(description = "Show or change if you bet against the point spread (admins only)")]
+    Spread(String),
+    #[command(
+        description = "Show or change if you also bet over/under the total points (admins only)"
+    )]
+    Totals(String),
+    #[command(
+        description = "Show or change if you pick every playoff series after the season (admins only)"
+    )]
+    Bracket(String),
+    #[command(
+        rename = "bracket_standings",
+        description = "Show the playoff bracket and the bracket challenge points"
+    )]
+    BracketStandings,
+    #[command(
+        description = "Show or change if you bet with buttons under each game instead of polls (admins only)"
+    )]
+    Keyboard(String),
+    #[command(description = "Cleanse the chat from toxic energy")]
+    Sage,
+    #[command(
+        rename = "stop_season",
+        description = "End the betting season and receive final standings. THIS CAN'T BE UNDONE!"
+    )]
+    StopSeason,
+    #[command(description = "Show this list of commands")]
+    Help,
+}
+
+/// The arguments of a command as words, e.g. ["custom", "5"] for /mode custom 5
+pub fn words(args: &str) -> Vec<&str> {
+    args.split_whitespace().collect()
+}
+
+/// The week a command asks for: a week number or "last" for the week before the ongoing one.
+/// None if the chat didn't play that week yet.
+pub fn parse_week(arg: &str, current_week: i32) -> Option<i32> {
+    let week = match arg {
+        "last" => current_week - 1,
+        _ => arg.parse().ok()?,
+    };
+
+    (1..=current_week).contains(&week).then(|| week)
+}
+
+/// The numbers of the open games from most to least confident, e.g. "3 1 2" or "3, 1, 2".
+/// None unless every number from 1 to number_of_games shows up exactly once.
+pub fn parse_confidence_order(args: &str, number_of_games: usize) -> Option<Vec<usize>> {
+    let order = args
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|number| !number.is_empty())
+        .map(|number| number.parse::<usize>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut sorted_order = order.clone();
+    sorted_order.sort_unstable();
+    (sorted_order == (1..=number_of_games).collect::<Vec<_>>()).then(|| order)
+}
+
+/// The commands that show a single week. Without a valid week they send a keyboard with the
+/// recent weeks, a click on it comes back as a callback query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeekCommand {
+    WeekStandings,
+    GameResults,
+}
+
+impl WeekCommand {
+    /// Most buttons in a row of the keyboard
+    const WEEKS_PER_ROW: usize = 6;
+    /// Weeks with a button, older ones have to be typed, e.g. /week_standings 4
+    pub const RECENT_WEEKS: i32 = 12;
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::WeekStandings => "week_standings",
+            Self::GameResults => "game_results",
+        }
+    }
+
+    fn button(&self, text: String, week: i32) -> InlineKeyboardButton {
+        // e.g. "week_standings 4"
+        InlineKeyboardButton::callback(text, format!("{} {}", self.name(), week))
+    }
+
+    /// The RECENT_WEEKS weeks up to max_week and a button for the last finished week below them
+    pub fn keyboard(&self, max_week: i32) -> InlineKeyboardMarkup {
+        let first_week = (max_week - Self::RECENT_WEEKS + 1).max(1);
+        let buttons = (first_week..=max_week)
+            .map(|week| self.button(week.to_string(), week))
+            .collect::<Vec<_>>();
+
+        let mut rows = buttons
+            .chunks(Self::WEEKS_PER_ROW)
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>();
+        if max_week > 1 {
+            rows.push(vec![self.button("last".to_string(), max_week - 1)]);
+        }
+
+        InlineKeyboardMarkup::new(rows)
+    }
+
+    /// Usage of the command with a week number, e.g. "/week_standings 4"
+    pub fn example(&self) -> String {
+        format!("/{} 4", self.name())
+    }
+
+    pub fn parse_callback_data(data: &str) -> Option<(Self, i32)> {
+        let mut words = data.split_whitespace();
+        let command = match words.next()? {
+            "week_standings" => Self::WeekStandings,
+            "game_results" => Self::GameResults,
+            _ => return None,
+        };
+
+        Some((command, words.next()?.parse().ok()?))
+    }
+}
+
+/// The command list for BotFather and the [commands] section of bot_info.txt,
+/// e.g. "start - Start your season! ..."
+pub fn botfather_commands() -> String {
+    Command::descriptions()
+        .lines()
+        .filter_map(|line| line.strip_prefix('/'))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::InlineKeyboardButtonKind;
+
+    #[test]
+    fn week_numbers_and_last_are_parsed() {
+        assert_eq!(parse_week("3", 5), Some(3));
+        assert_eq!(parse_week("5", 5), Some(5));
+        assert_eq!(parse_week("last", 5), Some(4));
+        assert_eq!(parse_week("last", 1), None);
+        assert_eq!(parse_week("0", 5), None);
+        assert_eq!(parse_week("6", 5), None);
+        assert_eq!(parse_week("-1", 5), None);
+        assert_eq!(parse_week("week", 5), None);
+        assert_eq!(parse_week("", 5), None);
+    }
+
+    #[test]
+    fn confidence_order_needs_every_game_once() {
+        assert_eq!(parse_confidence_order("3 1 2", 3), Some(vec![3, 1, 2]));
+        assert_eq!(parse_confidence_order("3, 1,2", 3), Some(vec![3, 1, 2]));
+        assert_eq!(parse_confidence_order("3 1", 3), None);
+        assert_eq!(parse_confidence_order("3 1 1", 3), None);
+        assert_eq!(parse_confidence_order("3 1 4", 3), None);
+        assert_eq!(parse_confidence_order("3 1 two", 3), None);
+        assert_eq!(parse_confidence_order("", 3), None);
+    }
+
+    #[test]
+    fn callback_data_of_the_week_keyboard_is_parsed() {
+        assert_eq!(
+            WeekCommand::parse_callback_data("week_standings 4"),
+            Some((WeekCommand::WeekStandings, 4))
+        );
+        assert_eq!(
+            WeekCommand::parse_callback_data("game_results 12"),
+            Some((WeekCommand::GameResults, 12))
+        );
+        assert_eq!(WeekCommand::parse_callback_data("game_results"), None);
+        assert_eq!(WeekCommand::parse_callback_data("game_results last"), None);
+        assert_eq!(WeekCommand::parse_callback_data("bet 1"), None);
+        assert_eq!(WeekCommand::parse_callback_data(""), None);
+    }
+
+    #[test]
+    fn week_keyboard_only_shows_recent_weeks() {
+        let callback_data = |max_week| {
+            WeekCommand::GameResults
+                .keyboard(max_week)
+                .inline_keyboard
+                .into_iter()
+                .flatten()
+                .map(|button| match button.kind {
+                    InlineKeyboardButtonKind::CallbackData(data) => (button.text, data),
+                    kind => panic!("unexpected button {:?}", kind),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let buttons = callback_data(85);
+        assert_eq!(buttons.len(), WeekCommand::RECENT_WEEKS as usize + 1);
+        assert_eq!(buttons[0].1, "game_results 74");
+        assert_eq!(buttons[11].1, "game_results 85");
+        assert_eq!(
+            buttons[12],
+            ("last".to_string(), "game_results 84".to_string())
+        );
+        // every button leads back to its week
+        for (_, data) in &buttons {
+            assert!(WeekCommand::parse_callback_data(data).is_some());
+        }
+
+        assert_eq!(
+            callback_data(1),
+            vec![("1".to_string(), "game_results 1".to_string())]
+        );
+    }
+
+    #[test]
+    fn bot_info_lists_every_command() {
+        let bot_info = include_str!("../bot_info.txt");
+        let commands = bot_info
+            .split("[commands]")
+            .nth(1)
+            .and_then(|section| section.split("[description]").next())
+            .expect("bot_info.txt has a [commands] section");
+
+        assert_eq!(commands.trim(), botfather_commands());
+    }
+}
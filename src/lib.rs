@@ -1,5 +1,5 @@
 pub mod utils;
-use sqlx::postgres::PgPool;
+use sqlx::postgres::{PgPool, PgPoolOptions};
 
 extern crate derive_more;
 use teloxide::RequestError;
@@ -19,6 +19,26 @@ pub enum Error {
     ChronoError(#[from] chrono::ParseError),
 }
 
+/// The database pool shared by everything the process does. Besides DATABASE_URL it reads
+/// DATABASE_MAX_CONNECTIONS (10 by default) and DATABASE_TIMEOUT_SECONDS, how long we wait for a
+/// free connection (5 by default). Connections are only opened once they are needed.
+pub fn connect_pool() -> Result<PgPool, Error> {
+    let setting = |name: &str, default: u64| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+
+    Ok(PgPoolOptions::new()
+        .max_connections(setting("DATABASE_MAX_CONNECTIONS", 10) as u32)
+        .connect_timeout(std::time::Duration::from_secs(setting(
+            "DATABASE_TIMEOUT_SECONDS",
+            5,
+        )))
+        .connect_lazy(&std::env::var("DATABASE_URL")?)?)
+}
+
 pub fn east_coast_date_today() -> Result<chrono::NaiveDate, Error> {
     let today_east_coast_delayed_format = chrono::Utc::now()
         .checked_sub_signed(chrono::Duration::hours(5))
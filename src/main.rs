@@ -1,15 +1,26 @@
 #![warn(clippy::all)]
 
+use commands::botfather_commands;
 use message_handling::run;
 use teloxide::prelude::*;
 use teloxide::requests::RequestWithFile;
 
+mod commands;
 mod message_handling;
 mod states;
 mod transitions;
 
+/// basketball_betting_bot
+///     runs the bot
+/// basketball_betting_bot commands
+///     prints the command list for BotFather and bot_info.txt
 #[tokio::main]
 async fn main() {
+    if std::env::args().nth(1).as_deref() == Some("commands") {
+        println!("{}", botfather_commands());
+        return;
+    }
+
     simple_logging::log_to_file("bot.log", log::LevelFilter::Info).unwrap();
     log::info!("Bot was started at {now}", now = chrono::Utc::now());
     run().await;
@@ -1,105 +1,272 @@
 use basketball_betting_bot::{
+    connect_pool,
     utils::{
-        add_bet, add_user, bet_to_team_id, get_chat_id_game_id_from_poll, poll_is_in_db_by_poll_id,
-        user_is_in_db,
+        add_bet, add_keyboard_bet, add_series_bet, add_total_bet, add_user, bet_to_team_id,
+        get_chat_id_game_id_from_poll, get_poll_type, keyboard_poll_id, parse_bet_callback_data,
+        poll_is_in_db_by_poll_id, redis_url, schedule_keyboard_game_update, series_poll_is_in_db,
+        show_game_results, show_week_rankings, user_is_in_db, KeyboardBet, PollType,
     },
     Error,
 };
 use sqlx::postgres::PgPool;
-use std::{convert::Infallible, env};
-use teloxide::prelude::*;
+use std::sync::Arc;
+use teloxide::{
+    dispatching::dialogue::{serializer::Bincode, InMemStorage, RedisStorage, Storage},
+    prelude::*,
+    types::User,
+};
 
-use crate::states::Dialogue;
+use crate::commands::WeekCommand;
+use crate::states::{Answer, Dialogue};
 
-type In = DialogueWithCx<Message, Dialogue, Infallible>;
+/// How often we try to store a poll answer while the database is unreachable
+const POLL_ANSWER_ATTEMPTS: u32 = 5;
 
 pub async fn run() {
     // create a new bot: env variable TELOXIDE_TOKEN must be set (bot token)
     let bot = Bot::builder().build();
+    // one pool for every update, the connections are opened when they are needed
+    let pool = connect_pool().expect("Could not create the database pool");
+    let bot_name = bot
+        .get_me()
+        .send()
+        .await
+        .expect("Could not get the bot's username")
+        .user
+        .username
+        .expect("The bot has no username");
+
+    // dialogues are kept in Redis, so a pending /stop_season or /week_standings survives a restart
+    match RedisStorage::open(redis_url(), Bincode).await {
+        Ok(storage) => dispatch(bot, pool, bot_name, storage).await,
+        Err(e) => {
+            log::error!(
+                "Could not connect to Redis, dialogues are only kept in memory: {}",
+                e
+            );
+            dispatch(bot, pool, bot_name, InMemStorage::new()).await
+        }
+    }
+}
+
+async fn dispatch<S>(bot: Bot, pool: PgPool, bot_name: String, storage: Arc<S>)
+where
+    S: Storage<Dialogue> + Send + Sync + 'static,
+    S::Error: std::fmt::Debug + Send + 'static,
+{
+    let poll_answers_pool = pool.clone();
+    let callback_queries_pool = pool.clone();
 
     Dispatcher::new(bot)
-        .messages_handler(DialogueDispatcher::new(
-            |DialogueWithCx { cx, dialogue }: In| async move {
-                let dialogue = dialogue.expect("std::convert::Infallible");
-                handle_message(cx, dialogue)
-                    .await
-                    .expect("Something wrong with the bot!")
+        .messages_handler(DialogueDispatcher::with_storage(
+            move |DialogueWithCx { cx, dialogue }: DialogueWithCx<Message, Dialogue, S::Error>| {
+                let pool = pool.clone();
+                let bot_name = bot_name.clone();
+                async move {
+                    // a dialogue that can't be read is dropped, the chat starts over with a command
+                    let dialogue = dialogue.unwrap_or_else(|e| {
+                        log::error!("Could not read dialogue: {:?}", e);
+                        Dialogue::default()
+                    });
+                    handle_message(cx, dialogue, pool, bot_name)
+                        .await
+                        .expect("Something wrong with the bot!")
+                }
             },
+            storage,
         ))
-        .poll_answers_handler(|rx: DispatcherHandlerRx<teloxide::types::PollAnswer>| {
-            rx.for_each_concurrent(None, |poll_answer| async move {
-                let pool = PgPool::connect(
-                    &env::var("DATABASE_URL")
-                        .expect("Could not find environment variable DATABASE_URL"),
-                )
-                .await
-                .expect("Could not establish connection do database");
-
-                handle_poll_answer(poll_answer, &pool)
-                    .await
-                    .unwrap_or_default();
-            })
-        })
+        .poll_answers_handler(
+            move |rx: DispatcherHandlerRx<teloxide::types::PollAnswer>| {
+                let pool = poll_answers_pool.clone();
+                rx.for_each_concurrent(None, move |poll_answer| {
+                    let pool = pool.clone();
+                    async move { store_poll_answer(poll_answer, &pool).await }
+                })
+            },
+        )
+        .callback_queries_handler(
+            move |rx: DispatcherHandlerRx<teloxide::types::CallbackQuery>| {
+                let pool = callback_queries_pool.clone();
+                rx.for_each_concurrent(None, move |callback_query| {
+                    let pool = pool.clone();
+                    async move {
+                        handle_callback_query(callback_query, &pool)
+                            .await
+                            .unwrap_or_else(|e| {
+                                log::error!("callback query could not be handled: {}", e);
+                            });
+                    }
+                })
+            },
+        )
         .dispatch()
         .await;
 }
 
-async fn handle_message(cx: UpdateWithCx<Message>, dialogue: Dialogue) -> TransitionOut<Dialogue> {
+/// A click on a button of a week keyboard, see WeekCommand, or on a team of a game sent with
+/// keyboard bets
+async fn handle_callback_query(
+    cx: UpdateWithCx<teloxide::types::CallbackQuery>,
+    pool: &PgPool,
+) -> Result<(), Error> {
+    let UpdateWithCx { bot, update: query } = cx;
+    let data = query.data.unwrap_or_default();
+    if let (Some(message), Some(option)) = (&query.message, parse_bet_callback_data(&data)) {
+        return handle_keyboard_bet(&bot, query.id, &query.from, message, option, pool).await;
+    }
+    // stops the loading animation of the button
+    bot.answer_callback_query(query.id).send().await?;
+
+    let message = match query.message {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let chat_id = message.chat_id();
+    // the results are sent to the chat of the keyboard
+    let cx = UpdateWithCx {
+        bot,
+        update: message,
+    };
+
+    match WeekCommand::parse_callback_data(&data) {
+        Some((WeekCommand::WeekStandings, week_number)) => {
+            show_week_rankings(&cx, pool, chat_id, week_number).await
+        }
+        Some((WeekCommand::GameResults, week_number)) => {
+            show_game_results(&cx, pool, chat_id, week_number).await
+        }
+        None => Ok(()),
+    }
+}
+
+/// The pick is stored like a poll answer and the game soon shows the new number of picks. The user
+/// gets a notification whether it counted.
+async fn handle_keyboard_bet(
+    bot: &Bot,
+    query_id: String,
+    user: &User,
+    message: &Message,
+    option: i32,
+    pool: &PgPool,
+) -> Result<(), Error> {
+    add_user_if_unknown(pool, user).await?;
+
+    let poll_id = keyboard_poll_id(message.chat_id(), message.id);
+    let keyboard_bet = add_keyboard_bet(pool, poll_id.clone(), user.id as i64, option).await?;
+    bot.answer_callback_query(query_id)
+        .text(match keyboard_bet {
+            KeyboardBet::Added => "Got your pick!",
+            KeyboardBet::Unchanged => "You already picked that team!",
+            KeyboardBet::Closed => "Betting is closed for this game!",
+        })
+        .send()
+        .await?;
+
+    // the edit is sent later, together with the other picks made in the meantime
+    if keyboard_bet == KeyboardBet::Added {
+        schedule_keyboard_game_update(pool.clone(), bot.clone(), poll_id);
+    }
+
+    Ok(())
+}
+
+async fn handle_message(
+    cx: UpdateWithCx<Message>,
+    dialogue: Dialogue,
+    pool: PgPool,
+    bot_name: String,
+) -> TransitionOut<Dialogue> {
     match cx.update.text_owned() {
         None => {
             //cx.answer_str("Send me a text message").await?;
             next(dialogue)
         }
-        Some(ans) => dialogue.react(cx, ans).await,
+        Some(text) => {
+            dialogue
+                .react(
+                    cx,
+                    Answer {
+                        text,
+                        pool,
+                        bot_name,
+                    },
+                )
+                .await
+        }
+    }
+}
+
+/// The pool already waits a while for a connection, if the database is still unreachable the
+/// answer is retried a few times, so a short outage doesn't cost anyone their bet
+async fn store_poll_answer(cx: UpdateWithCx<teloxide::types::PollAnswer>, pool: &PgPool) {
+    for attempt in 1..=POLL_ANSWER_ATTEMPTS {
+        match handle_poll_answer(&cx, pool).await {
+            Err(Error::SqlxError(sqlx::Error::PoolTimedOut))
+            | Err(Error::SqlxError(sqlx::Error::Io(_)))
+                if attempt < POLL_ANSWER_ATTEMPTS =>
+            {
+                tokio::time::delay_for(std::time::Duration::from_secs(10)).await;
+            }
+            Err(e) => {
+                log::error!(
+                    "poll answer of user {} to poll {} could not be stored: {}",
+                    cx.update.user.id,
+                    cx.update.poll_id,
+                    e
+                );
+                return;
+            }
+            Ok(_) => return,
+        }
     }
 }
 
 async fn handle_poll_answer(
-    cx: UpdateWithCx<teloxide::types::PollAnswer>,
+    cx: &UpdateWithCx<teloxide::types::PollAnswer>,
     pool: &PgPool,
 ) -> Result<(), Error> {
-    dbg!(&cx.update.option_ids);
     // check if it's a poll that the bot sent
     // is probably unnecessary, since per the official docs poll answers not sent by the bot itself
     // are ignored. Since this could change in the future, I'm gonna play it safe.
-    if !poll_is_in_db_by_poll_id(pool, cx.update.poll_id.clone())
-        .await
-        .expect("could not get poll_id!")
-    {
-        dbg!("poll_id not found!", cx.update.poll_id);
+    let is_series_poll = series_poll_is_in_db(pool, cx.update.poll_id.clone()).await?;
+    if !is_series_poll && !poll_is_in_db_by_poll_id(pool, cx.update.poll_id.clone()).await? {
+        log::debug!("answer to unknown poll {}", cx.update.poll_id);
         return Ok(());
     }
-    let (chat_id, game_id) = get_chat_id_game_id_from_poll(pool, cx.update.poll_id.clone())
-        .await
-        .expect("Could not get chat_id");
 
-    if !user_is_in_db(pool, cx.update.user.id as i64)
-        .await
-        .expect("Could not determine if user is in database")
-    {
-        dbg!("adding user to db");
-        add_user(
+    add_user_if_unknown(pool, &cx.update.user).await?;
+
+    if cx.update.option_ids.is_empty() {
+        return Ok(());
+    }
+
+    if is_series_poll {
+        return add_series_bet(
             pool,
+            cx.update.poll_id.clone(),
             cx.update.user.id as i64,
-            cx.update.user.first_name,
-            cx.update.user.last_name.unwrap_or_else(|| "".to_string()),
-            cx.update.user.username.unwrap_or_else(|| "".to_string()),
-            cx.update
-                .user
-                .language_code
-                .unwrap_or_else(|| "en".to_string()),
+            cx.update.option_ids[0],
         )
-        .await?;
+        .await;
     }
 
-    if cx.update.option_ids.is_empty() {
+    let (chat_id, game_id) = get_chat_id_game_id_from_poll(pool, cx.update.poll_id.clone()).await?;
+
+    // the first option of a totals poll is the over, the second one the under
+    if get_poll_type(pool, cx.update.poll_id.clone()).await? == PollType::Total {
+        add_total_bet(
+            pool,
+            game_id,
+            chat_id,
+            cx.update.user.id as i64,
+            cx.update.option_ids[0] == 0,
+            cx.update.poll_id.clone(),
+        )
+        .await?;
         return Ok(());
     }
 
-    let bet = bet_to_team_id(pool, cx.update.option_ids[0], game_id)
-        .await
-        .expect("Could not convert bet to team_id");
-    dbg!(bet);
+    let bet = bet_to_team_id(pool, cx.update.option_ids[0], game_id).await?;
 
     add_bet(
         pool,
@@ -107,9 +274,29 @@ async fn handle_poll_answer(
         chat_id,
         cx.update.user.id as i64,
         bet,
-        cx.update.poll_id,
+        cx.update.poll_id.clone(),
     )
     .await?;
 
     Ok(())
 }
+
+/// Bets reference their user, so everyone who bets is added to the users table first
+async fn add_user_if_unknown(pool: &PgPool, user: &User) -> Result<(), Error> {
+    if !user_is_in_db(pool, user.id as i64).await? {
+        log::debug!("adding user {} to db", user.id);
+        add_user(
+            pool,
+            user.id as i64,
+            user.first_name.clone(),
+            user.last_name.clone().unwrap_or_default(),
+            user.username.clone().unwrap_or_default(),
+            user.language_code
+                .clone()
+                .unwrap_or_else(|| "en".to_string()),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
@@ -1,12 +1,20 @@
 use teloxide_macros::Transition;
 
 use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+/// What every state gets with a message: its text, the database pool and the username of the bot
+pub struct Answer {
+    pub text: String,
+    pub pool: PgPool,
+    /// commands in groups look like /standings@BasketballBettingBot
+    pub bot_name: String,
+}
 
 #[derive(Transition, derive_more::From, Serialize, Deserialize)]
 pub enum Dialogue {
     Stop(StopState),
     Ready(ReadyState),
-    WeekResults(WeekInputState),
 }
 
 impl Default for Dialogue {
@@ -20,8 +28,3 @@ pub struct StopState;
 
 #[derive(Serialize, Deserialize)]
 pub struct ReadyState;
-
-#[derive(Serialize, Deserialize)]
-pub struct WeekInputState {
-    pub max_week_number: i32,
-}
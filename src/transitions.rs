@@ -1,34 +1,47 @@
 #![warn(clippy::all)]
 
+use crate::commands::{parse_confidence_order, parse_week, words, Command, WeekCommand};
 use crate::states::*;
 use crate::*;
 use basketball_betting_bot::{
     get_active_chat_status,
     utils::{
         cache_to_games, change_active_chat_status, chat_is_known, east_coast_date_in_x_days,
-        get_bet_week, get_games, remove_chat, send_polls, show_all_bets_season,
-        show_complete_rankings, show_game_results, show_week_rankings, user_is_admin,
+        find_teams, free_confidence_values, get_active_season, get_against_the_spread,
+        get_bet_week, get_bracket_challenge, get_game_mode, get_games_for_mode, get_keyboard_bets,
+        get_open_polls, get_pinned_teams, get_ranking_system, get_time_zone, get_totals_polls,
+        include_pinned_teams, pin_team, remove_chat, send_polls, set_against_the_spread,
+        set_bracket_challenge, set_confidence, set_game_mode, set_keyboard_bets,
+        set_ranking_system, set_time_zone, set_totals_polls, show_all_bets_season,
+        show_bracket_standings, show_complete_rankings, show_confidence_rankings,
+        show_game_results, show_official_rankings, show_week_rankings, show_weighted_rankings,
+        unpin_team, user_is_admin, GameMode, RankingSystem, DEFAULT_TIME_ZONE,
     },
 };
+use chrono_tz::Tz;
 use sqlx::postgres::PgPool;
-use std::env;
+use teloxide::utils::command::BotCommand;
 use teloxide_macros::teloxide;
 
 #[teloxide(subtransition)]
-async fn ready(_state: ReadyState, cx: TransitionIn, ans: String) -> TransitionOut<Dialogue> {
+async fn ready(_state: ReadyState, cx: TransitionIn, answer: Answer) -> TransitionOut<Dialogue> {
     dbg!("READY");
-    let pool = PgPool::connect(
-        &env::var("DATABASE_URL").expect("Could not find DATABASE_URL environment variable!"),
-    )
-    .await;
-
-    if let Err(e) = pool {
-        dbg!(e);
+    let Answer {
+        text: ans,
+        pool,
+        bot_name,
+    } = answer;
+    // commands can have arguments, e.g. /mode custom 5. Anything else, like commands for other
+    // bots in the group, is none of our business and doesn't need the database
+    let command = match Command::parse(&ans, bot_name.as_str()) {
+        Ok(command) => command,
+        Err(_) => return next(ReadyState),
+    };
+
+    if !db_is_available(&cx, &pool).await {
         return next(ReadyState);
     }
 
-    let pool = pool.unwrap();
-
     let chat_id = cx.chat_id();
     let chat_is_known = chat_is_known(&pool, chat_id).await.unwrap_or(false);
     if !chat_is_known {
@@ -41,24 +54,19 @@ async fn ready(_state: ReadyState, cx: TransitionIn, ans: String) -> TransitionO
         .unwrap_or_default();
     }
 
-    let ans = ans.as_str();
-
-    // if the chat was not yet marked as active and they send a message other than start
+    // if the chat was not yet marked as active and they send a command other than start
     // we'll send them to the SetupState where they can
     if !get_active_chat_status(&pool, chat_id)
         .await
         .unwrap_or(false)
-        && (ans != "/start" && ans != "/start@BasketballBettingBot")
+        && command != Command::Start
     {
         cx.answer_str("Send /start to begin your season!").await?;
         return next(ReadyState);
     }
 
-    dbg!(ans);
-    dbg!(chat_id);
-    dbg!(chrono::Utc::now().naive_utc());
-    match ans {
-        "/start" | "/start@BasketballBettingBot" => {
+    match command {
+        Command::Start => {
             let chat_id = cx.update.chat_id();
             log::info!("COMMAND: /start, chat_id: {}", chat_id);
             if get_active_chat_status(&pool, chat_id)
@@ -69,27 +77,37 @@ async fn ready(_state: ReadyState, cx: TransitionIn, ans: String) -> TransitionO
                     .await?;
                 return next(ReadyState);
             }
-            cx.answer_str(r#"
-BasketballBettingBot sends you 11 NBA games to bet on each week, 10 good ones and one battle between the supreme tank commanders. 
+            let game_mode = get_game_mode(&pool, chat_id).await.unwrap_or_default();
+            cx.answer_str(format!(r#"
+BasketballBettingBot sends you {games}. 
 The one who gets the most games right in a week gets one point.
 You play against the other members of your group.
 The overall winner is the one with the most weekly wins (/full_standings) or the one with the most correct bets overall (/all_bets). Your Call.
+Group admins can make one of them official with /ranking.
 
 To get a list of all commands the bot understands, send /help
-"#).await?;
+"#, games = game_mode.games_description())).await?;
             cx.answer_str("Your season begins now!").await?;
 
-            let mut games = cache_to_games().unwrap_or_default();
-            if games.len() < 11 {
-                games = get_games(
-                    &pool,
-                    10,
-                    east_coast_date_in_x_days(1, false).unwrap(),
-                    east_coast_date_in_x_days(7, false).unwrap(),
-                )
+            let (start_date, end_date) = get_active_season(&pool)
                 .await
-                .unwrap_or_default();
-            }
+                .and_then(|season| season.upcoming_bet_week())
+                .unwrap_or_else(|_| {
+                    (
+                        east_coast_date_in_x_days(1, false).unwrap(),
+                        east_coast_date_in_x_days(7, false).unwrap(),
+                    )
+                });
+
+            let games = match cache_to_games(game_mode, start_date, end_date).await {
+                Ok(games) => games,
+                Err(_) => get_games_for_mode(&pool, game_mode, start_date, end_date)
+                    .await
+                    .unwrap_or_default(),
+            };
+            let games = include_pinned_teams(&pool, chat_id, &games, start_date, end_date)
+                .await
+                .unwrap_or(games);
 
             send_polls(&pool, chat_id, &cx.bot, &games)
                 .await
@@ -103,14 +121,151 @@ To get a list of all commands the bot understands, send /help
             return next(ReadyState);
         }
 
-        "/standings" | "/standings@BasketballBettingBot" => {
+        Command::Standings => {
             let chat_id = cx.update.chat_id();
             log::info!("COMMAND: /standings, chat_id: {}", chat_id);
-            show_week_rankings(&cx, &pool, chat_id, -1)
+            match get_ranking_system(&pool, chat_id).await.unwrap_or_default() {
+                RankingSystem::Weekly => show_week_rankings(&cx, &pool, chat_id, -1)
+                    .await
+                    .unwrap_or_default(),
+                RankingSystem::Confidence => show_confidence_rankings(&cx, &pool, chat_id)
+                    .await
+                    .unwrap_or_default(),
+                RankingSystem::UpsetBonus => show_weighted_rankings(&cx, &pool, chat_id)
+                    .await
+                    .unwrap_or_default(),
+                RankingSystem::PerGame => show_all_bets_season(&pool, &cx, chat_id)
+                    .await
+                    .unwrap_or_default(),
+            }
+        }
+        Command::ConfidenceStandings => {
+            let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /confidence_standings, chat_id: {}", chat_id);
+            show_confidence_rankings(&cx, &pool, chat_id)
                 .await
                 .unwrap_or_default();
         }
-        "/full_standings" | "/full_standings@BasketballBettingBot" => {
+        Command::UpsetStandings => {
+            let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /upset_standings, chat_id: {}", chat_id);
+            show_weighted_rankings(&cx, &pool, chat_id)
+                .await
+                .unwrap_or_default();
+        }
+        Command::BracketStandings => {
+            let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /bracket_standings, chat_id: {}", chat_id);
+            show_bracket_standings(&cx, &pool, chat_id)
+                .await
+                .unwrap_or_default();
+        }
+        Command::Confidence(args) => {
+            let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /confidence, chat_id: {}", chat_id);
+            if get_ranking_system(&pool, chat_id).await.unwrap_or_default()
+                != RankingSystem::Confidence
+            {
+                cx.answer_str("Your chat doesn't play with confidence points!\nGroup admins can turn them on with /ranking confidence")
+                    .await?;
+                return next(ReadyState);
+            }
+
+            let user_id = match cx.update.from() {
+                Some(user) => user.id as i64,
+                None => return next(ReadyState),
+            };
+            let bet_week = get_bet_week(&pool, chat_id).await;
+            let open_polls = match &bet_week {
+                Ok(bet_week) => get_open_polls(&pool, chat_id, bet_week.id).await,
+                Err(_) => Ok(Vec::new()),
+            };
+            let (bet_week, open_polls) = match (bet_week, open_polls) {
+                (Ok(bet_week), Ok(open_polls)) if !open_polls.is_empty() => (bet_week, open_polls),
+                (Ok(_), Ok(_)) => {
+                    cx.answer_str("There are no games left to rank this week!")
+                        .await?;
+                    return next(ReadyState);
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    log::error!(
+                        "/confidence could not get the games of chat {}: {}",
+                        chat_id,
+                        e
+                    );
+                    cx.answer_str("Sorry, could not get your games right now!")
+                        .await?;
+                    return next(ReadyState);
+                }
+            };
+
+            // the ranking comes with the command, so it also works in groups with privacy mode.
+            // Without a valid one the open games are listed with their numbers.
+            let order = match parse_confidence_order(&args, open_polls.len()) {
+                Some(order) => order,
+                None => {
+                    let mut games = String::new();
+                    for (number, (_, game)) in open_polls.iter().enumerate() {
+                        games.push_str(&format!("{}. {}\n", number + 1, game));
+                    }
+                    let example = (1..=open_polls.len())
+                        .rev()
+                        .map(|number| number.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    cx.answer_str(format!(
+                        "{invalid}Rank your picks from most to least confident!\nSend /confidence with the numbers of all games in that order, e.g. /confidence {example}\n\n{games}",
+                        invalid = match args.trim().is_empty() {
+                            true => String::new(),
+                            false => format!(
+                                "Please use every number from 1 to {} exactly once!\n\n",
+                                open_polls.len()
+                            ),
+                        },
+                        example = example,
+                        games = games
+                    ))
+                    .await?;
+                    return next(ReadyState);
+                }
+            };
+
+            let free_values = free_confidence_values(&pool, user_id, bet_week.id)
+                .await
+                .unwrap_or_default();
+            if free_values.len() < order.len() {
+                cx.answer_str("Sorry, could not save your ranking right now!")
+                    .await?;
+                return next(ReadyState);
+            }
+
+            // the most confident pick gets the highest value that isn't used by a game that tipped off
+            let picks = order
+                .iter()
+                .zip(free_values)
+                .map(|(number, confidence)| (open_polls[number - 1].0.clone(), confidence))
+                .collect::<Vec<_>>();
+
+            match set_confidence(&pool, chat_id, user_id, bet_week.id, &picks).await {
+                Ok(_) => {
+                    let mut ranking = String::from("Your confidence points:\n");
+                    for ((_, points), number) in picks.iter().zip(order) {
+                        ranking.push_str(&format!("{} -> {}\n", points, open_polls[number - 1].1));
+                    }
+                    cx.answer_str(ranking).await?;
+                }
+                Err(e) => {
+                    log::error!(
+                        "/confidence could not save the ranking in chat {}: {}",
+                        chat_id,
+                        e
+                    );
+                    cx.answer_str("Sorry, could not save your ranking right now!")
+                        .await?;
+                }
+            }
+        }
+        Command::FullStandings => {
             let chat_id = cx.update.chat_id();
             log::info!("COMMAND: /full_standings, chat_id: {}", chat_id);
             show_complete_rankings(&cx, &pool, chat_id)
@@ -118,29 +273,45 @@ To get a list of all commands the bot understands, send /help
                 .unwrap_or_default();
         }
 
-        "/all_bets" | "/all_bets@BasketballBettingBot" => {
+        Command::AllBets => {
             let chat_id = cx.update.chat_id();
             log::info!("COMMAND: /all_bets, chat_id: {}", chat_id);
             show_all_bets_season(&pool, &cx, chat_id)
                 .await
                 .unwrap_or_default();
         }
-        "/game_results" | "/game_results@BasketballBettingBot" => {
+        Command::GameResults(args) => {
             let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /game_results, chat_id: {}", chat_id);
             let bet_week = get_bet_week(&pool, chat_id).await;
 
             match bet_week {
                 Err(e) => {
-                    dbg!(e);
+                    log::error!(
+                        "/game_results could not get the bet week of chat {}: {}",
+                        chat_id,
+                        e
+                    );
                     cx.answer_str("Sorry, could not send standings right now!")
                         .await?;
                 }
-                Ok(bet_week) => show_game_results(&cx, &pool, chat_id, bet_week.week_number)
-                    .await
-                    .unwrap_or_default(),
+                // the ongoing week if no week is given
+                Ok(bet_week) if args.trim().is_empty() => {
+                    show_game_results(&cx, &pool, chat_id, bet_week.week_number)
+                        .await
+                        .unwrap_or_default()
+                }
+                Ok(bet_week) => match parse_week(args.trim(), bet_week.week_number) {
+                    Some(week_number) => show_game_results(&cx, &pool, chat_id, week_number)
+                        .await
+                        .unwrap_or_default(),
+                    None => {
+                        ask_for_week(&cx, WeekCommand::GameResults, bet_week.week_number).await?
+                    }
+                },
             }
         }
-        "/stop_season" | "/stop_season@BasketballBettingBot" => {
+        Command::StopSeason => {
             let chat_id = cx.update.chat_id();
             log::info!("COMMAND: /stop_season, chat_id: {}", chat_id);
             if user_is_admin(chat_id, &cx).await.unwrap_or(false) {
@@ -157,48 +328,302 @@ Send /continue to go on!",
                     .await?;
             }
         }
-        "/week_standings" | "/week_standings@BasketballBettingBot" => {
+        Command::Mode(args) => {
+            let args = words(&args);
             let chat_id = cx.update.chat_id();
-            log::info!("COMMAND: /week_standings, chat_id: {}", chat_id);
-            let bet_week = get_bet_week(&pool, chat_id).await;
+            log::info!("COMMAND: /mode, chat_id: {}", chat_id);
+            if args.is_empty() {
+                let game_mode = get_game_mode(&pool, chat_id).await.unwrap_or_default();
+                cx.answer_str(format!(
+                    "Current game mode: {game_mode}\n
+Group admins can change the mode with:
+/mode full -> every game of the week
+/mode best_of -> the 10 best games of the week + 1 tank battle
+/mode custom N -> the N best games of the week + 1 tank battle (N from 1 to {max_games})",
+                    game_mode = game_mode,
+                    max_games = GameMode::MAX_CUSTOM_GAMES
+                ))
+                .await?;
+                return next(ReadyState);
+            }
 
-            match bet_week {
-                Err(e) => {
-                    dbg!(e);
-                    cx.answer_str("Sorry, could not send standings right now!")
+            if !user_is_admin(chat_id, &cx).await.unwrap_or(false) {
+                cx.answer_str("Only the group admins can change the game mode!")
+                    .await?;
+                return next(ReadyState);
+            }
+
+            match GameMode::parse(&args) {
+                Some(game_mode) => match set_game_mode(&pool, chat_id, game_mode).await {
+                    Ok(_) => {
+                        cx.answer_str(format!(
+                            "Game mode changed to {}!\nThe new mode applies from the next bet week on.",
+                            game_mode
+                        ))
                         .await?;
+                    }
+                    Err(e) => {
+                        log::error!("/mode could not be changed in chat {}: {}", chat_id, e);
+                        cx.answer_str("Sorry, could not change the game mode right now!")
+                            .await?;
+                    }
+                },
+                None => {
+                    cx.answer_str(
+                        "I don't know that mode!\nSend /mode to see all available modes.",
+                    )
+                    .await?;
                 }
-                Ok(bet_week) => {
-                    let max_week = bet_week.week_number;
-                    match max_week {
-                        1 => {
-                            cx.answer_str("You haven't played more than one week yet!\nHere are the standings for that week:")
+            }
+        }
+        Command::Ranking(args) => {
+            let args = words(&args);
+            let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /ranking, chat_id: {}", chat_id);
+            if args.is_empty() {
+                let ranking_system = get_ranking_system(&pool, chat_id).await.unwrap_or_default();
+                cx.answer_str(format!(
+                    "Official ranking system: {ranking_system}\nSee the standings with {command}\n
+Group admins can change the ranking system with:
+/ranking weekly -> the winner is the one with the most weeks won
+/ranking per_game -> the winner is the one with the most correct bets overall
+/ranking confidence -> the winner is the one with the most confidence points overall, rank your picks with /confidence
+/ranking upset_bonus -> the winner is the one with the most points overall, correct picks on underdogs earn bonus points",
+                    ranking_system = ranking_system,
+                    command = ranking_system.command()
+                ))
+                .await?;
+                return next(ReadyState);
+            }
+
+            if !user_is_admin(chat_id, &cx).await.unwrap_or(false) {
+                cx.answer_str("Only the group admins can change the ranking system!")
+                    .await?;
+                return next(ReadyState);
+            }
+
+            match RankingSystem::parse(&args) {
+                Some(ranking_system) => {
+                    match set_ranking_system(&pool, chat_id, ranking_system).await {
+                        Ok(_) => {
+                            cx.answer_str(format!(
+                                "Official ranking system changed to {}!\nSee the standings with {}",
+                                ranking_system,
+                                ranking_system.command()
+                            ))
+                            .await?;
+                        }
+                        Err(e) => {
+                            log::error!("/ranking could not be changed in chat {}: {}", chat_id, e);
+                            cx.answer_str("Sorry, could not change the ranking system right now!")
                                 .await?;
-                            show_week_rankings(&cx, &pool, chat_id, 1)
-                                .await
-                                .unwrap_or_default();
                         }
-                        _ => {
-                            let mut week_options = String::from("");
-                            for week in 1..=max_week {
-                                week_options.push_str(
-                                    format!("/{week_number}\t\t\t", week_number = week).as_str(),
-                                )
-                            }
+                    }
+                }
+                None => {
+                    cx.answer_str(
+                        "I don't know that ranking system!\nSend /ranking to see all available ranking systems.",
+                    )
+                    .await?;
+                }
+            }
+        }
+        Command::Timezone(args) => {
+            let args = words(&args);
+            let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /timezone, chat_id: {}", chat_id);
+            if args.is_empty() {
+                let time_zone = get_time_zone(&pool, chat_id)
+                    .await
+                    .unwrap_or(DEFAULT_TIME_ZONE);
+                cx.answer_str(format!(
+                    "Game times are shown in {time_zone}.\n
+Group admins can change the time zone with /timezone <name>, e.g. /timezone Europe/Berlin
+All names: https://en.wikipedia.org/wiki/List_of_tz_database_time_zones",
+                    time_zone = time_zone.name()
+                ))
+                .await?;
+                return next(ReadyState);
+            }
+
+            if !user_is_admin(chat_id, &cx).await.unwrap_or(false) {
+                cx.answer_str("Only the group admins can change the time zone!")
+                    .await?;
+                return next(ReadyState);
+            }
+
+            match args[0].parse::<Tz>() {
+                Ok(time_zone) => match set_time_zone(&pool, chat_id, time_zone).await {
+                    Ok(_) => {
+                        cx.answer_str(format!("Game times are now shown in {}!", time_zone.name()))
+                            .await?;
+                    }
+                    Err(e) => {
+                        log::error!("/timezone could not be changed in chat {}: {}", chat_id, e);
+                        cx.answer_str("Sorry, could not change the time zone right now!")
+                            .await?;
+                    }
+                },
+                Err(_) => {
+                    cx.answer_str(
+                        "I don't know that time zone!\nUse a name like America/Los_Angeles or Europe/Berlin.",
+                    )
+                    .await?;
+                }
+            }
+        }
+        Command::Spread(args) => {
+            log::info!("COMMAND: /spread, chat_id: {}", chat_id);
+            let pool = &pool;
+            answer_toggle(
+                &cx,
+                &args,
+                &SPREAD_TOGGLE,
+                get_against_the_spread(pool, chat_id),
+                move |on| set_against_the_spread(pool, chat_id, on),
+            )
+            .await?;
+        }
+        Command::Totals(args) => {
+            log::info!("COMMAND: /totals, chat_id: {}", chat_id);
+            let pool = &pool;
+            answer_toggle(
+                &cx,
+                &args,
+                &TOTALS_TOGGLE,
+                get_totals_polls(pool, chat_id),
+                move |on| set_totals_polls(pool, chat_id, on),
+            )
+            .await?;
+        }
+        Command::Bracket(args) => {
+            log::info!("COMMAND: /bracket, chat_id: {}", chat_id);
+            let pool = &pool;
+            answer_toggle(
+                &cx,
+                &args,
+                &BRACKET_TOGGLE,
+                get_bracket_challenge(pool, chat_id),
+                move |on| set_bracket_challenge(pool, chat_id, on),
+            )
+            .await?;
+        }
+        Command::Keyboard(args) => {
+            log::info!("COMMAND: /keyboard, chat_id: {}", chat_id);
+            let pool = &pool;
+            answer_toggle(
+                &cx,
+                &args,
+                &KEYBOARD_TOGGLE,
+                get_keyboard_bets(pool, chat_id),
+                move |on| set_keyboard_bets(pool, chat_id, on),
+            )
+            .await?;
+        }
+        Command::PinTeam(ref args) | Command::UnpinTeam(ref args) => {
+            let chat_id = cx.update.chat_id();
+            let pin = matches!(command, Command::PinTeam(_));
+            log::info!("COMMAND: {:?}, chat_id: {}", command, chat_id);
+            let args = words(args);
+            if args.is_empty() {
+                let pinned_teams = get_pinned_teams(&pool, chat_id)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(_, name)| name)
+                    .collect::<Vec<_>>();
+                let pinned_teams = match pinned_teams.is_empty() {
+                    true => "No teams are pinned yet.".to_string(),
+                    false => format!("Pinned teams:\n{}", pinned_teams.join("\n")),
+                };
+                cx.answer_str(format!(
+                    "{pinned_teams}\n
+Pinned teams are part of your bets every week they play.
+Group admins can change them with /pin_team <name> and /unpin_team <name>, e.g. /pin_team Celtics",
+                    pinned_teams = pinned_teams
+                ))
+                .await?;
+                return next(ReadyState);
+            }
+
+            if !user_is_admin(chat_id, &cx).await.unwrap_or(false) {
+                cx.answer_str("Only the group admins can pin teams!")
+                    .await?;
+                return next(ReadyState);
+            }
+
+            let teams = find_teams(&pool, &args.join(" ")).await.unwrap_or_default();
+            match teams.as_slice() {
+                [] => {
+                    cx.answer_str("I don't know that team!").await?;
+                }
+                [(team_id, name)] => {
+                    let changed = match pin {
+                        true => pin_team(&pool, chat_id, *team_id).await,
+                        false => unpin_team(&pool, chat_id, *team_id).await,
+                    };
+                    match (changed, pin) {
+                        (Ok(_), true) => {
                             cx.answer_str(format!(
-                                "Click on the week that you want to show the results for!\n{week_options}",
-                                week_options=week_options
+                                "The {} are part of your bets from the next week on!",
+                                name
                             ))
                             .await?;
-                            return next(WeekInputState {
-                                max_week_number: max_week,
-                            });
+                        }
+                        (Ok(_), false) => {
+                            cx.answer_str(format!("The {} are no longer pinned!", name))
+                                .await?;
+                        }
+                        (Err(e), _) => {
+                            log::error!(
+                                "pinned teams could not be changed in chat {}: {}",
+                                chat_id,
+                                e
+                            );
+                            cx.answer_str("Sorry, could not change the pinned teams right now!")
+                                .await?;
                         }
                     }
                 }
+                _ => {
+                    let names = teams.into_iter().map(|(_, name)| name).collect::<Vec<_>>();
+                    cx.answer_str(format!("Which one do you mean?\n{}", names.join("\n")))
+                        .await?;
+                }
+            }
+        }
+        Command::WeekStandings(args) => {
+            let chat_id = cx.update.chat_id();
+            log::info!("COMMAND: /week_standings, chat_id: {}", chat_id);
+            let bet_week = get_bet_week(&pool, chat_id).await;
+
+            match bet_week {
+                Err(e) => {
+                    log::error!(
+                        "/week_standings could not get the bet week of chat {}: {}",
+                        chat_id,
+                        e
+                    );
+                    cx.answer_str("Sorry, could not send standings right now!")
+                        .await?;
+                }
+                // nothing to pick if there is only one week
+                Ok(bet_week) if args.trim().is_empty() && bet_week.week_number == 1 => {
+                    show_week_rankings(&cx, &pool, chat_id, 1)
+                        .await
+                        .unwrap_or_default();
+                }
+                Ok(bet_week) => match parse_week(args.trim(), bet_week.week_number) {
+                    Some(week_number) => show_week_rankings(&cx, &pool, chat_id, week_number)
+                        .await
+                        .unwrap_or_default(),
+                    None => {
+                        ask_for_week(&cx, WeekCommand::WeekStandings, bet_week.week_number).await?
+                    }
+                },
             }
         }
-        "/sage" | "/sage@BasketballBettingBot" => {
+        Command::Sage => {
             log::info!("COMMAND: /sage, chat_id: {}", cx.update.chat_id());
             let photo = teloxide::types::InputFile::Url(
                 "https://media.giphy.com/media/zLVTQRSiCm2a8kljMq/giphy.gif".to_string(),
@@ -207,62 +632,47 @@ Send /continue to go on!",
             match cx.answer_animation(photo).send().await {
                 Ok(_) => (),
                 Err(e) => {
-                    dbg!(e);
+                    log::error!("/sage could not send the GIF to chat {}: {}", chat_id, e);
                     cx.answer_str("Sorry, could not send the GIF, try again later!")
                         .await?;
                 }
             }
         }
-        "/help" | "/help@BasketballBettingBot" => {
-            cx.answer_str(r#"
-BasketballBettingBot sends you 11 NBA games to bet on each week, 10 good ones and one battle between the supreme tank commanders. 
+        Command::Help => {
+            let game_mode = get_game_mode(&pool, chat_id).await.unwrap_or_default();
+            cx.answer_str(format!(
+                r#"
+BasketballBettingBot sends you {games}. Group admins can change which games with /mode.
 The one who gets the most games right in a week gets one point.
 You play against the other members of your group.
 The overall winner is the one with the most weekly wins (/full_standings) or the one with the most correct bets overall (/all_bets). Your Call.
+Group admins can make one of them official with /ranking.
 
 Results are updated live during the games. 
 
-/standings 
--> Show standings for the ongoing week
-
-/full_standings 
--> Show standings for the whole season
-
-/all_bets 
--> Show fraction of correct bets for the whole season 
-(Alternative to weekly standings)
-
-/week_standings 
--> Show standings for a specified week
-
-/sage 
--> Cleanse the chat from toxic energy
-
-/stop_season 
--> End the betting season and receive final standings
-THIS CAN'T BE UNDONE!
-
-
-"#).await?;
+{commands}"#,
+                games = game_mode.games_description(),
+                commands = Command::descriptions()
+            ))
+            .await?;
         }
-        _ => (),
     }
 
     next(ReadyState)
 }
 
 #[teloxide(subtransition)]
-async fn stop_season(_state: StopState, cx: TransitionIn, ans: String) -> TransitionOut<Dialogue> {
-    let pool = PgPool::connect(
-        &env::var("DATABASE_URL").expect("Could not find DATABASE_URL environment variable!"),
-    )
-    .await;
-
-    if let Err(e) = pool {
-        dbg!(e);
+async fn stop_season(
+    _state: StopState,
+    cx: TransitionIn,
+    answer: Answer,
+) -> TransitionOut<Dialogue> {
+    let Answer {
+        text: ans, pool, ..
+    } = answer;
+    if !db_is_available(&cx, &pool).await {
         return next(ReadyState);
     }
-    let pool = pool.expect("Could not establish DB connection!");
 
     dbg!("StopState");
     let chat_id = cx.update.chat_id();
@@ -280,10 +690,8 @@ async fn stop_season(_state: StopState, cx: TransitionIn, ans: String) -> Transi
             show_week_rankings(&cx, &pool, chat_id, -1)
                 .await
                 .unwrap_or_default();
-            show_complete_rankings(&cx, &pool, chat_id)
-                .await
-                .unwrap_or_default();
-            show_all_bets_season(&pool, &cx, chat_id)
+            cx.answer_str("FINAL STANDINGS").await?;
+            show_official_rankings(&cx, &pool, chat_id)
                 .await
                 .unwrap_or_default();
             remove_chat(&pool, chat_id).await.unwrap_or_default();
@@ -296,40 +704,200 @@ async fn stop_season(_state: StopState, cx: TransitionIn, ans: String) -> Transi
     next(ReadyState)
 }
 
-#[teloxide(subtransition)]
-async fn send_week_results(
-    state: WeekInputState,
-    cx: TransitionIn,
-    ans: String,
-) -> TransitionOut<Dialogue> {
-    dbg!("WeekInputState");
-    let pool = PgPool::connect(
-        &env::var("DATABASE_URL").expect("Could not find DATABASE_URL environment variable!"),
-    )
-    .await;
-
-    if let Err(e) = pool {
-        dbg!(e);
-        return next(ReadyState);
-    }
-    let pool = pool.expect("Could not establish DB connection!");
-
-    let week_number = ans.strip_prefix("/").unwrap_or("-1").parse::<i32>();
-    let max_week = state.max_week_number;
-    match week_number {
-        Ok(week_number) => {
-            if week_number <= max_week && week_number >= 1 {
-                show_week_rankings(&cx, &pool, cx.update.chat_id(), week_number)
-                    .await
-                    .unwrap_or_default();
-            } else {
-                cx.answer_str("You haven't played that week number yet!")
-                    .await?;
-            }
+/// If a connection of the pool can be used, tells the chat to try again later otherwise
+async fn db_is_available(cx: &TransitionIn, pool: &PgPool) -> bool {
+    let message = match pool.acquire().await {
+        Ok(_) => return true,
+        Err(sqlx::Error::PoolTimedOut) => {
+            "Lots of bets coming in right now, please try again in a minute!"
+        }
+        Err(e) => {
+            log::error!("database is not available: {}", e);
+            "Sorry, can't reach the database right now, please try again later!"
         }
+    };
+    if let Err(e) = cx.answer_str(message).await {
+        log::error!("could not answer chat {}: {}", cx.chat_id(), e);
+    }
+
+    false
+}
+
+/// Sends a keyboard with the recent weeks of the chat, the picked week is handled as a callback
+/// query
+async fn ask_for_week(
+    cx: &TransitionIn,
+    command: WeekCommand,
+    max_week: i32,
+) -> Result<(), RequestError> {
+    if max_week < 1 {
+        cx.answer_str("You haven't played a week yet!").await?;
+        return Ok(());
+    }
+
+    let text = match max_week > WeekCommand::RECENT_WEEKS {
+        true => format!(
+            "Click on the week that you want to show the results for! Older weeks can be picked with the week number, e.g. {}",
+            command.example()
+        ),
+        false => "Click on the week that you want to show the results for!".to_string(),
+    };
+    cx.answer(text)
+        .reply_markup(command.keyboard(max_week))
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+/// The answers of a setting of the chat that is either on or off, e.g. /spread
+struct Toggle {
+    command: &'static str,
+    /// the current setting, for on and off
+    status: [&'static str; 2],
+    /// what /<command> on and /<command> off do
+    usage: [&'static str; 2],
+    /// the answers once it's changed
+    changed: [&'static str; 2],
+    not_admin: &'static str,
+    failed: &'static str,
+}
+
+const SPREAD_TOGGLE: Toggle = Toggle {
+    command: "spread",
+    status: [
+        "You bet against the spread, e.g. Boston Celtics -6.5 only wins if the Celtics win by 7 or more.",
+        "You bet on the winner of each game.",
+    ],
+    usage: [
+        "bet against the point spread",
+        "bet on the winner of each game",
+    ],
+    changed: [
+        "You bet against the spread from the next week on!",
+        "You bet on the winner of each game from the next week on!",
+    ],
+    not_admin: "Only the group admins can change how you bet!",
+    failed: "Sorry, could not change how you bet right now!",
+};
+
+const TOTALS_TOGGLE: Toggle = Toggle {
+    command: "totals",
+    status: [
+        "You get a second poll for every game to bet on the total points, e.g. Over 221.5 or Under 221.5.",
+        "You only bet on the winner of each game.",
+    ],
+    usage: [
+        "also bet over/under the total points of each game",
+        "only bet on the winner of each game",
+    ],
+    changed: [
+        "You get totals polls from the next week on!\nThe standings show your correct totals bets next to your points.",
+        "You only bet on the winner of each game from the next week on!",
+    ],
+    not_admin: "Only the group admins can change how you bet!",
+    failed: "Sorry, could not change how you bet right now!",
+};
+
+const BRACKET_TOGGLE: Toggle = Toggle {
+    command: "bracket",
+    status: [
+        "You play the bracket challenge: once the regular season is over you get a poll for every playoff series to pick the winner and the number of games.",
+        "You don't play the bracket challenge.",
+    ],
+    usage: [
+        "pick every playoff series, later rounds are worth more points",
+        "no polls once the regular season is over",
+    ],
+    changed: [
+        "You play the bracket challenge!\nA series is worth 1 point in the first round, 2 in the conference semifinals, 4 in the conference finals and 8 in the finals. Getting the number of games right as well doubles the points.\nCheck the bracket with /bracket_standings",
+        "You don't play the bracket challenge anymore!",
+    ],
+    not_admin: "Only the group admins can change the bracket challenge!",
+    failed: "Sorry, could not change the bracket challenge right now!",
+};
+
+const KEYBOARD_TOGGLE: Toggle = Toggle {
+    command: "keyboard",
+    status: [
+        "Every game is sent as a message with a button for each team. You can change your pick until the game starts.",
+        "Every game is sent as a poll.",
+    ],
+    usage: [
+        "pick a team with the buttons under each game, the picks so far are shown",
+        "pick a team in a poll for each game",
+    ],
+    changed: [
+        "You bet with buttons from the next week on!\nTotals and bracket picks are still sent as polls.",
+        "You bet with polls from the next week on!",
+    ],
+    not_admin: "Only the group admins can change how you bet!",
+    failed: "Sorry, could not change how you bet right now!",
+};
+
+/// Without an argument the current setting is shown, group admins change it with on or off
+async fn answer_toggle<S, F>(
+    cx: &TransitionIn,
+    args: &str,
+    toggle: &Toggle,
+    is_on: impl std::future::Future<Output = Result<bool, basketball_betting_bot::Error>>,
+    set: S,
+) -> Result<(), RequestError>
+where
+    S: FnOnce(bool) -> F,
+    F: std::future::Future<Output = Result<(), basketball_betting_bot::Error>>,
+{
+    // the first entry of the texts is for on, the second one for off
+    let index = |on: bool| if on { 0 } else { 1 };
+    let chat_id = cx.update.chat_id();
+    let args = words(args);
+    if args.is_empty() {
+        let is_on = is_on.await.unwrap_or(false);
+        cx.answer_str(format!(
+            "{status}\n
+Group admins can change it with:
+/{command} on -> {on_usage}
+/{command} off -> {off_usage}",
+            status = toggle.status[index(is_on)],
+            command = toggle.command,
+            on_usage = toggle.usage[0],
+            off_usage = toggle.usage[1]
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    if !user_is_admin(chat_id, cx).await.unwrap_or(false) {
+        cx.answer_str(toggle.not_admin).await?;
+        return Ok(());
+    }
+
+    let on = match args[0] {
+        "on" => true,
+        "off" => false,
         _ => {
-            cx.answer_str("Please enter a valid number").await?;
+            cx.answer_str(format!(
+                "Send /{command} on or /{command} off!",
+                command = toggle.command
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+    match set(on).await {
+        Ok(_) => {
+            cx.answer_str(toggle.changed[index(on)]).await?;
+        }
+        Err(e) => {
+            log::error!(
+                "/{} could not be changed in chat {}: {}",
+                toggle.command,
+                chat_id,
+                e
+            );
+            cx.answer_str(toggle.failed).await?;
         }
     }
-    next(ReadyState)
+
+    Ok(())
 }
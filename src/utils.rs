@@ -1,12 +1,19 @@
 use crate::Error;
 use chrono::prelude::*;
 use chrono::Duration;
+use chrono_tz::Tz;
 use num_traits::cast::ToPrimitive;
-use redis::Commands;
+use once_cell::sync::OnceCell;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPool, query};
 use teloxide::prelude::*;
+use teloxide::types::{ChatId, ChatOrInlineMessage, InlineKeyboardButton, InlineKeyboardMarkup};
 use teloxide::KnownApiErrorKind;
 
+/// Time zone of chats that didn't pick one with /timezone
+pub const DEFAULT_TIME_ZONE: Tz = chrono_tz::America::New_York;
+
 fn east_coast_date_today() -> Result<chrono::NaiveDate, Error> {
     let today_east_coast_delayed_format = chrono::Utc::now()
         .checked_sub_signed(chrono::Duration::hours(4))
@@ -64,13 +71,31 @@ pub async fn send_polls(
         format!("Sending polls! (send_polls()), chat_id: {}", chat_id)
     );
     let bet_week = get_bet_week(pool, chat_id).await?;
+    let season = get_active_season(pool).await?;
     let tomorrow = east_coast_date_in_x_days(1, false)?;
     let today = east_coast_date_in_x_days(0, true)?;
 
-    if today > chrono::NaiveDate::parse_from_str("2021-05-09", "%Y-%m-%d")? {
-        log::info!("{}", "Not sending polls - season ended!".to_string());
+    // the phase of the upcoming bet week decides how long it lasts
+    let phase = season.phase(tomorrow);
+    match phase {
+        SeasonPhase::RegularSeason if today > season.last_poll_date() => {
+            log::info!(
+                "{}",
+                "Not sending polls - regular season ended!".to_string()
+            );
 
-        return Ok(());
+            return Ok(());
+        }
+        SeasonPhase::Over => {
+            log::info!("{}", "Not sending polls - season ended!".to_string());
+
+            return Ok(());
+        }
+        // there are days without games in the playoffs, they don't get a bet week
+        SeasonPhase::PlayIn | SeasonPhase::Playoffs if games.is_empty() => {
+            return Ok(());
+        }
+        _ => (),
     }
 
     // if week_number is 0 it's the first time polls are sent to the chat
@@ -78,30 +103,101 @@ pub async fn send_polls(
     // the upcoming week right away
     // if today is the last day of a bet_week, we want to send out new polls for the upcoming week
     if bet_week.week_number == 0 || tomorrow > bet_week.end_date {
-        if bet_week.end_date > chrono::NaiveDate::parse_from_str("2021-05-09", "%Y-%m-%d")? {
-            bot.send_message(chat_id, "This is the last week of the NBA season!")
+        if phase == SeasonPhase::RegularSeason && bet_week.end_date > season.last_poll_date() {
+            bot.send_message(chat_id, "This is the last week of the NBA regular season!")
                 .send()
                 .await?;
         }
         let week_number = bet_week.week_number + 1;
 
-        let bet_week_id = insert_bet_week(
-            pool,
-            chat_id,
-            week_number,
-            east_coast_date_in_x_days(1, false)?,
-            east_coast_date_in_x_days(7, false)?,
-            true,
-        )
-        .await?;
+        let (start_date, end_date) = season.upcoming_bet_week()?;
+        let bet_week_id =
+            insert_bet_week(pool, chat_id, week_number, start_date, end_date, true).await?;
 
+        let time_zone = get_time_zone(pool, chat_id).await?;
+        let against_the_spread = get_against_the_spread(pool, chat_id).await?
+            && spreads_available(pool, chat_id, bot, games).await?;
+        let totals_polls = get_totals_polls(pool, chat_id).await?;
+        let keyboard_bets = get_keyboard_bets(pool, chat_id).await?;
         for game in games {
-            send_game(&pool, game.id, chat_id, game, &bot, bet_week_id).await?;
+            if keyboard_bets {
+                send_keyboard_game(
+                    &pool,
+                    chat_id,
+                    game,
+                    &bot,
+                    bet_week_id,
+                    time_zone,
+                    against_the_spread,
+                )
+                .await?;
+            } else {
+                send_game(
+                    &pool,
+                    chat_id,
+                    game,
+                    &bot,
+                    bet_week_id,
+                    time_zone,
+                    against_the_spread,
+                )
+                .await?;
+            }
+            if totals_polls {
+                send_total_poll(&pool, chat_id, game, &bot, bet_week_id, time_zone).await?;
+            }
         }
     }
     Ok(())
 }
 
+/// Chats that bet against the spread are told about every game of the week that has no line, it's
+/// sent as a straight up poll. If no game has one, e.g. because covers.com changed its markup,
+/// betting against the spread is turned off until the chat turns it on again.
+async fn spreads_available(
+    pool: &PgPool,
+    chat_id: i64,
+    bot: &teloxide::Bot,
+    games: &[Game],
+) -> anyhow::Result<bool> {
+    let mut games_without_spread = Vec::new();
+    for game in games {
+        if get_home_spread(pool, game.id).await?.is_none() {
+            games_without_spread.push(format!("{} @ {}", game.away_team, game.home_team));
+        }
+    }
+
+    if games_without_spread.is_empty() {
+        return Ok(true);
+    }
+
+    let all_games_without_spread = games_without_spread.len() == games.len();
+    let message = match all_games_without_spread {
+        true => {
+            log::warn!(
+                "no point spreads for the games of chat {}, turning off betting against the spread",
+                chat_id
+            );
+            set_against_the_spread(pool, chat_id, false).await?;
+            "There are no point spreads for this week's games, so you bet on the winner of each game. Betting against the spread is turned off, send /spread on to turn it on again.".to_string()
+        }
+        false => format!(
+            "There is no point spread for these games yet, you bet on the winner:\n{}",
+            games_without_spread.join("\n")
+        ),
+    };
+    // the polls are sent anyway, the bet week is already stored
+    if let Err(e) = bot.send_message(chat_id, message).send().await {
+        log::error!(
+            "could not tell chat {} about the missing spreads: {}",
+            chat_id,
+            e
+        );
+    }
+
+    Ok(!all_games_without_spread)
+}
+
 async fn insert_bet_week(
     pool: &PgPool,
     chat_id: i64,
@@ -195,42 +291,326 @@ pub async fn get_bet_week(pool: &PgPool, chat_id: i64) -> Result<BetWeek, Error>
     }
 }
 
-async fn _get_number_of_games_for_chat(pool: &PgPool, chat_id: i64) -> anyhow::Result<i64> {
-    let number_of_games = query!(
-        "SELECT number_of_games FROM full_chat_information WHERE chat_id = $1",
-        chat_id
+pub async fn get_active_season(pool: &PgPool) -> Result<Season, Error> {
+    let row = query!(
+        r#"
+        SELECT
+            id
+            ,year
+            ,regular_season_start
+            ,regular_season_end
+            ,play_in_start
+            ,playoffs_start
+            ,playoffs_end
+        FROM seasons
+        WHERE is_active = True
+        "#
     )
     .fetch_one(pool)
-    .await
-    .unwrap()
-    .number_of_games;
+    .await?;
+
+    Ok(Season {
+        id: row.id,
+        year: row.year,
+        regular_season_start: row.regular_season_start,
+        regular_season_end: row.regular_season_end,
+        play_in_start: row.play_in_start,
+        playoffs_start: row.playoffs_start,
+        playoffs_end: row.playoffs_end,
+    })
+}
+
+/// Makes the given season the active one. If the season's year is already in the table its dates
+/// are overwritten, all other seasons are deactivated.
+pub async fn start_new_season(pool: &PgPool, season: &Season) -> Result<i32, Error> {
+    let mut tx = pool.begin().await?;
+
+    query!("UPDATE seasons SET is_active = False WHERE is_active = True")
+        .execute(&mut tx)
+        .await?;
+
+    let row = query!(
+        r#"
+        INSERT INTO seasons(year, regular_season_start, regular_season_end, play_in_start, playoffs_start, playoffs_end, is_active) VALUES
+        ($1, $2, $3, $4, $5, $6, True)
+        ON CONFLICT (year) DO
+            UPDATE SET (regular_season_start, regular_season_end, play_in_start, playoffs_start, playoffs_end, is_active) = ($2, $3, $4, $5, $6, True)
+        RETURNING id;
+        "#,
+        season.year,
+        season.regular_season_start,
+        season.regular_season_end,
+        season.play_in_start,
+        season.playoffs_start,
+        season.playoffs_end
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(row.id)
+}
+
+pub async fn get_game_mode(pool: &PgPool, chat_id: i64) -> Result<GameMode, Error> {
+    let row = query!(
+        "SELECT game_mode, number_of_games FROM full_chat_information WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // chats that are not in the db yet play the default mode
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(GameMode::default()),
+    };
+    let number_of_games = row.number_of_games.unwrap_or(10) as i64;
+
+    match row.game_mode.as_deref() {
+        Some("full") => Ok(GameMode::Full),
+        Some("custom") => Ok(GameMode::Custom(number_of_games)),
+        _ => Ok(GameMode::BestOf(number_of_games)),
+    }
+}
+
+pub async fn set_game_mode(pool: &PgPool, chat_id: i64, game_mode: GameMode) -> Result<(), Error> {
+    // the number of games is only stored for the custom mode, the others use the game_modes table
+    let number_of_games = match game_mode {
+        GameMode::Custom(number_of_games) => Some(number_of_games as i32),
+        _ => None,
+    };
+
+    query!(
+        "UPDATE chats SET game_mode = $1, number_of_games = $2 WHERE id = $3",
+        game_mode.id(),
+        number_of_games,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_ranking_system(pool: &PgPool, chat_id: i64) -> Result<RankingSystem, Error> {
+    let row = query!(
+        "SELECT ranking_system FROM full_chat_information WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match row.and_then(|row| row.ranking_system).as_deref() {
+        Some("per_game") => Ok(RankingSystem::PerGame),
+        Some("confidence") => Ok(RankingSystem::Confidence),
+        Some("upset_bonus") => Ok(RankingSystem::UpsetBonus),
+        _ => Ok(RankingSystem::Weekly),
+    }
+}
+
+pub async fn set_ranking_system(
+    pool: &PgPool,
+    chat_id: i64,
+    ranking_system: RankingSystem,
+) -> Result<(), Error> {
+    query!(
+        "UPDATE chats SET ranking_system = $1 WHERE id = $2",
+        ranking_system.id(),
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_time_zone(pool: &PgPool, chat_id: i64) -> Result<Tz, Error> {
+    let row = query!("SELECT time_zone FROM chats WHERE id = $1", chat_id)
+        .fetch_optional(pool)
+        .await?;
+
+    // the time zone is checked before it's stored, so parsing only fails if someone messed with the db
+    Ok(row
+        .and_then(|row| row.time_zone.parse::<Tz>().ok())
+        .unwrap_or(DEFAULT_TIME_ZONE))
+}
+
+pub async fn set_time_zone(pool: &PgPool, chat_id: i64, time_zone: Tz) -> Result<(), Error> {
+    query!(
+        "UPDATE chats SET time_zone = $1 WHERE id = $2",
+        time_zone.name(),
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_against_the_spread(pool: &PgPool, chat_id: i64) -> Result<bool, Error> {
+    let row = query!(
+        "SELECT against_the_spread FROM chats WHERE id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| row.against_the_spread).unwrap_or(false))
+}
+
+pub async fn set_against_the_spread(
+    pool: &PgPool,
+    chat_id: i64,
+    against_the_spread: bool,
+) -> Result<(), Error> {
+    query!(
+        "UPDATE chats SET against_the_spread = $1 WHERE id = $2",
+        against_the_spread,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_totals_polls(pool: &PgPool, chat_id: i64) -> Result<bool, Error> {
+    let row = query!("SELECT totals_polls FROM chats WHERE id = $1", chat_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| row.totals_polls).unwrap_or(false))
+}
+
+pub async fn set_totals_polls(
+    pool: &PgPool,
+    chat_id: i64,
+    totals_polls: bool,
+) -> Result<(), Error> {
+    query!(
+        "UPDATE chats SET totals_polls = $1 WHERE id = $2",
+        totals_polls,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_bracket_challenge(pool: &PgPool, chat_id: i64) -> Result<bool, Error> {
+    let row = query!("SELECT bracket_challenge FROM chats WHERE id = $1", chat_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| row.bracket_challenge).unwrap_or(false))
+}
+
+pub async fn set_bracket_challenge(
+    pool: &PgPool,
+    chat_id: i64,
+    bracket_challenge: bool,
+) -> Result<(), Error> {
+    query!(
+        "UPDATE chats SET bracket_challenge = $1 WHERE id = $2",
+        bracket_challenge,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_keyboard_bets(pool: &PgPool, chat_id: i64) -> Result<bool, Error> {
+    let row = query!("SELECT keyboard_bets FROM chats WHERE id = $1", chat_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| row.keyboard_bets).unwrap_or(false))
+}
+
+pub async fn set_keyboard_bets(
+    pool: &PgPool,
+    chat_id: i64,
+    keyboard_bets: bool,
+) -> Result<(), Error> {
+    query!(
+        "UPDATE chats SET keyboard_bets = $1 WHERE id = $2",
+        keyboard_bets,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// e.g. "-6.5", "+3" or "PK" if neither team is favoured
+pub fn spread_string(spread: f32) -> String {
+    match spread == 0.0 {
+        true => "PK".to_string(),
+        false => format!("{:+}", spread),
+    }
+}
 
-    Ok(number_of_games.unwrap() as i64)
+/// e.g. "Sun, 2021-01-10"
+pub fn local_date(date_time: chrono::DateTime<Utc>, time_zone: Tz) -> String {
+    date_time
+        .with_timezone(&time_zone)
+        .format("%a, %Y-%m-%d")
+        .to_string()
+}
+
+/// e.g. "07:30 PM EST"
+pub fn local_time(date_time: chrono::DateTime<Utc>, time_zone: Tz) -> String {
+    date_time
+        .with_timezone(&time_zone)
+        .format("%I:%M %p %Z")
+        .to_string()
+}
+
+/// Returns the games of the bet week from start_date to end_date according to the game mode
+pub async fn get_games_for_mode(
+    pool: &PgPool,
+    game_mode: GameMode,
+    start_date: chrono::NaiveDate,
+    end_date: chrono::NaiveDate,
+) -> anyhow::Result<Vec<Game>> {
+    match game_mode {
+        GameMode::Full => get_all_games(pool, start_date, end_date).await,
+        GameMode::BestOf(number_of_games) | GameMode::Custom(number_of_games) => {
+            get_games(pool, number_of_games, start_date, end_date).await
+        }
+    }
 }
 
 async fn send_game(
     pool: &PgPool,
-    game_id: i32,
     chat_id: i64,
     game: &Game,
     bot: &teloxide::Bot,
     bet_week_id: i32,
+    time_zone: Tz,
+    against_the_spread: bool,
 ) -> anyhow::Result<()> {
-    if poll_is_in_db(&pool, game_id, chat_id).await? {
+    if poll_is_in_db(&pool, game.id, chat_id, PollType::Winner).await? {
         eprintln!("entry already in polls table!");
         return Ok(());
     } else {
+        // games without a line yet are sent as straight up polls
+        let home_spread = match against_the_spread {
+            true => get_home_spread(pool, game.id).await?,
+            false => None,
+        };
+        let options = game_options(&game.away_team, &game.home_team, home_spread);
+
         let poll = bot
             .send_poll(
                 chat_id,
-                format!(
-                    "{away_team} @ {home_team} \n{date_string}\n{time_string} ET",
-                    home_team = game.home_team,
-                    away_team = game.away_team,
-                    date_string = game.date_string,
-                    time_string = game.time_string
-                ),
-                vec![game.away_team.to_string(), game.home_team.to_string()],
+                game_question(&game.away_team, &game.home_team, game.date_time, time_zone),
+                options,
             )
             .disable_notification(true)
             .is_anonymous(false)
@@ -241,7 +621,16 @@ async fn send_game(
             let poll_id = poll.poll().expect("").id.to_owned();
             let local_id = poll.id;
 
-            add_poll(&pool, poll_id, local_id, chat_id, game.id, bet_week_id).await?;
+            add_poll(
+                &pool,
+                poll_id,
+                local_id,
+                chat_id,
+                game.id,
+                bet_week_id,
+                home_spread,
+            )
+            .await?;
         } else {
             eprintln!(
                 "POLL in chat {chat_id} could not be sent",
@@ -252,73 +641,493 @@ async fn send_game(
     Ok(())
 }
 
-pub async fn poll_is_in_db(pool: &PgPool, game_id: i32, chat_id: i64) -> Result<bool, Error> {
-    query!(
-        r#"
-        SELECT EXISTS(
-            SELECT * 
-            FROM polls
-            WHERE game_id = $1
-            AND chat_id = $2
-        ) AS exists
-        ;
-        "#,
-        game_id,
-        chat_id
+/// e.g. "Boston Celtics @ Los Angeles Lakers \nSun, 2021-01-10\n07:30 PM EST"
+fn game_question(
+    away_team: &str,
+    home_team: &str,
+    date_time: chrono::DateTime<Utc>,
+    time_zone: Tz,
+) -> String {
+    format!(
+        "{away_team} @ {home_team} \n{date_string}\n{time_string}",
+        home_team = home_team,
+        away_team = away_team,
+        date_string = local_date(date_time, time_zone),
+        time_string = local_time(date_time, time_zone)
     )
-    .fetch_one(pool)
-    .await?
-    .exists
-    .ok_or(Error::SqlxError(sqlx::Error::RowNotFound))
 }
 
-pub async fn poll_is_in_db_by_poll_id(pool: &PgPool, poll_id: String) -> Result<bool, Error> {
-    query!(
-        "SELECT EXISTS(SELECT id from polls WHERE id = $1);",
-        poll_id
-    )
-    .fetch_one(pool)
-    .await?
-    .exists
-    .ok_or(Error::SqlxError(sqlx::Error::RowNotFound))
+/// The away team first, then the home team, both with their spread if the game has one
+fn game_options(away_team: &str, home_team: &str, home_spread: Option<f32>) -> Vec<String> {
+    match home_spread {
+        Some(home_spread) => vec![
+            format!("{} {}", away_team, spread_string(-home_spread)),
+            format!("{} {}", home_team, spread_string(home_spread)),
+        ],
+        None => vec![away_team.to_string(), home_team.to_string()],
+    }
 }
 
-async fn add_poll(
+/// Sends the game as a message with a button for each team instead of a poll, for chats with
+/// keyboard bets. The message is stored as a poll with the id from keyboard_poll_id.
+async fn send_keyboard_game(
     pool: &PgPool,
-    poll_id: String,
-    local_id: i32,
     chat_id: i64,
-    game_id: i32,
+    game: &Game,
+    bot: &teloxide::Bot,
     bet_week_id: i32,
+    time_zone: Tz,
+    against_the_spread: bool,
 ) -> anyhow::Result<()> {
-    let date_east_coast = east_coast_date_today()?;
-
-    query!(
-        r#"
-        INSERT INTO polls(id,local_id, chat_id, game_id, poll_sent_date, bet_week_id) VALUES 
-        ($1, $2, $3, $4, $5, $6);
-        "#,
-        poll_id,
-        local_id,
-        chat_id,
-        game_id,
-        date_east_coast,
-        bet_week_id
-    )
-    .execute(pool)
-    .await?;
+    if poll_is_in_db(&pool, game.id, chat_id, PollType::Winner).await? {
+        log::warn!("keyboard game {} already sent to chat {}", game.id, chat_id);
+        return Ok(());
+    }
 
-    Ok(())
-}
+    let home_spread = match against_the_spread {
+        true => get_home_spread(pool, game.id).await?,
+        false => None,
+    };
+    let options = game_options(&game.away_team, &game.home_team, home_spread);
+    let question = game_question(&game.away_team, &game.home_team, game.date_time, time_zone);
 
-pub async fn get_games(
-    pool: &PgPool,
-    number_of_games: i64,
+    let message = bot
+        .send_message(
+            chat_id,
+            keyboard_game_text(&question, &options, [0, 0], true),
+        )
+        .reply_markup(bet_keyboard(&options))
+        .disable_notification(true)
+        .send()
+        .await;
+
+    match message {
+        Ok(message) => {
+            let poll_id = keyboard_poll_id(chat_id, message.id);
+            add_poll(
+                &pool,
+                poll_id.clone(),
+                message.id,
+                chat_id,
+                game.id,
+                bet_week_id,
+                home_spread,
+            )
+            .await?;
+            query!("UPDATE polls SET is_keyboard = True WHERE id = $1", poll_id)
+                .execute(pool)
+                .await?;
+        }
+        Err(e) => {
+            log::error!(
+                "keyboard game {} could not be sent to chat {}: {}",
+                game.id,
+                chat_id,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The id a game sent with keyboard bets has in the polls table, e.g. "keyboard -1001234 56"
+pub fn keyboard_poll_id(chat_id: i64, message_id: i32) -> String {
+    format!("keyboard {} {}", chat_id, message_id)
+}
+
+/// The game with the number of picks for each team, a lock is put in front once betting is closed
+fn keyboard_game_text(
+    question: &str,
+    options: &[String],
+    picks: [i64; 2],
+    is_open: bool,
+) -> String {
+    let picks = options
+        .iter()
+        .zip(picks.iter())
+        .map(|(option, picks)| format!("{}: {}", option, picks))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "{lock}{question}\n\nPicks:\n{picks}",
+        lock = if is_open { "" } else { "🔒 " },
+        question = question,
+        picks = picks
+    )
+}
+
+/// One button per team. Like the options of a poll a click on the away team comes back as the
+/// callback query "bet 0" and one on the home team as "bet 1".
+fn bet_keyboard(options: &[String]) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![options
+        .iter()
+        .enumerate()
+        .map(|(option, text)| {
+            InlineKeyboardButton::callback(text.clone(), format!("bet {}", option))
+        })
+        .collect::<Vec<_>>()])
+}
+
+/// The option of a click on a button of bet_keyboard, None for any other callback query
+pub fn parse_bet_callback_data(data: &str) -> Option<i32> {
+    match data {
+        "bet 0" => Some(0),
+        "bet 1" => Some(1),
+        _ => None,
+    }
+}
+
+/// Edits a game sent with keyboard bets to show the current picks. Once betting is closed the
+/// buttons are removed.
+pub async fn update_keyboard_game(
+    pool: &PgPool,
+    bot: &teloxide::Bot,
+    poll_id: String,
+) -> Result<(), Error> {
+    let poll = query!(
+        r#"
+        SELECT
+            polls.chat_id
+            ,polls.local_id
+            ,polls.home_spread
+            ,polls.is_open
+            ,games.date_time
+            ,away.name AS away_team
+            ,home.name AS home_team
+            ,(SELECT COUNT(*) FROM bets WHERE bets.poll_id = polls.id AND bets.bet = games.away_team) AS away_picks
+            ,(SELECT COUNT(*) FROM bets WHERE bets.poll_id = polls.id AND bets.bet = games.home_team) AS home_picks
+        FROM polls
+        JOIN games ON games.id = polls.game_id
+        JOIN teams AS away ON away.id = games.away_team
+        JOIN teams AS home ON home.id = games.home_team
+        WHERE polls.id = $1
+        "#,
+        poll_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let chat_id = poll.chat_id.unwrap_or(-1);
+    let time_zone = get_time_zone(pool, chat_id).await?;
+    let away_team = poll.away_team.unwrap_or_default();
+    let home_team = poll.home_team.unwrap_or_default();
+    let options = game_options(&away_team, &home_team, poll.home_spread);
+    let question = game_question(
+        &away_team,
+        &home_team,
+        poll.date_time.ok_or(sqlx::Error::RowNotFound)?,
+        time_zone,
+    );
+    let is_open = poll.is_open.unwrap_or(false);
+    let picks = [
+        poll.away_picks.unwrap_or_default(),
+        poll.home_picks.unwrap_or_default(),
+    ];
+    // an empty keyboard removes the buttons
+    let keyboard = match is_open {
+        true => bet_keyboard(&options),
+        false => InlineKeyboardMarkup::default(),
+    };
+
+    let text = keyboard_game_text(&question, &options, picks, is_open);
+
+    // Telegram rate limits the edits of a chat, when it asks us to wait we do and try again
+    let mut attempt = 1;
+    loop {
+        let edit = bot
+            .edit_message_text(
+                ChatOrInlineMessage::Chat {
+                    chat_id: ChatId::Id(chat_id),
+                    message_id: poll.local_id.unwrap_or_default(),
+                },
+                text.clone(),
+            )
+            .reply_markup(keyboard.clone())
+            .send()
+            .await;
+        match edit {
+            Ok(_)
+            | Err(RequestError::ApiError {
+                kind: teloxide::ApiErrorKind::Known(KnownApiErrorKind::MessageNotModified),
+                ..
+            }) => return Ok(()),
+            Err(RequestError::RetryAfter(seconds)) if attempt < KEYBOARD_EDIT_ATTEMPTS => {
+                log::warn!(
+                    "editing keyboard game {} is rate limited, retrying in {}s",
+                    poll_id,
+                    seconds
+                );
+                tokio::time::delay_for(std::time::Duration::from_secs(seconds.max(1) as u64)).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+const KEYBOARD_EDIT_ATTEMPTS: u32 = 3;
+/// Clicks on the same game within this delay are shown with a single edit
+const KEYBOARD_UPDATE_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// The keyboard games with an edit waiting to be sent
+static PENDING_KEYBOARD_UPDATES: OnceCell<std::sync::Mutex<std::collections::HashSet<String>>> =
+    OnceCell::new();
+
+/// Schedules an update_keyboard_game for the poll, unless one is pending already. The edit is
+/// sent after KEYBOARD_UPDATE_DELAY and shows every pick stored until then, so a burst of clicks
+/// results in one edit of the message instead of one each.
+pub fn schedule_keyboard_game_update(pool: PgPool, bot: teloxide::Bot, poll_id: String) {
+    let pending = PENDING_KEYBOARD_UPDATES.get_or_init(Default::default);
+    {
+        let mut pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+        if !pending.insert(poll_id.clone()) {
+            return;
+        }
+    }
+
+    tokio::spawn(async move {
+        tokio::time::delay_for(KEYBOARD_UPDATE_DELAY).await;
+        // removed before reading the picks, so a click stored from now on gets an edit of its own
+        pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&poll_id);
+        if let Err(e) = update_keyboard_game(&pool, &bot, poll_id.clone()).await {
+            log::error!("keyboard game {} could not be updated: {}", poll_id, e);
+        }
+    });
+}
+
+/// Sends the over/under poll of the game, games without a line are skipped
+async fn send_total_poll(
+    pool: &PgPool,
+    chat_id: i64,
+    game: &Game,
+    bot: &teloxide::Bot,
+    bet_week_id: i32,
+    time_zone: Tz,
+) -> anyhow::Result<()> {
+    if poll_is_in_db(&pool, game.id, chat_id, PollType::Total).await? {
+        log::warn!(
+            "totals poll of game {} already sent to chat {}",
+            game.id,
+            chat_id
+        );
+        return Ok(());
+    }
+
+    let (total, is_scraped) = match get_total(pool, game.id).await? {
+        Some(total) => total,
+        None => return Ok(()),
+    };
+    // the average isn't a bookmaker's line, the chat should know what it bets on
+    let line_note = match is_scraped {
+        true => "",
+        false => "\n(no betting line yet, computed from the teams' average points)",
+    };
+
+    let poll = bot
+        .send_poll(
+            chat_id,
+            format!(
+                "{away_team} @ {home_team} \nTotal points\n{date_string}\n{time_string}{line_note}",
+                home_team = game.home_team,
+                away_team = game.away_team,
+                date_string = local_date(game.date_time, time_zone),
+                time_string = local_time(game.date_time, time_zone),
+                line_note = line_note
+            ),
+            vec![format!("Over {}", total), format!("Under {}", total)],
+        )
+        .disable_notification(true)
+        .is_anonymous(false)
+        .send()
+        .await;
+
+    match poll {
+        Ok(poll) => {
+            let poll_id = poll.poll().expect("").id.to_owned();
+            let local_id = poll.id;
+
+            add_total_poll(
+                &pool,
+                poll_id,
+                local_id,
+                chat_id,
+                game.id,
+                bet_week_id,
+                total,
+            )
+            .await?;
+        }
+        Err(e) => {
+            log::error!(
+                "totals poll of game {} could not be sent to chat {}: {}",
+                game.id,
+                chat_id,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn poll_is_in_db(
+    pool: &PgPool,
+    game_id: i32,
+    chat_id: i64,
+    poll_type: PollType,
+) -> Result<bool, Error> {
+    query!(
+        r#"
+        SELECT EXISTS(
+            SELECT * 
+            FROM polls
+            WHERE game_id = $1
+            AND chat_id = $2
+            AND poll_type = $3
+        ) AS exists
+        ;
+        "#,
+        game_id,
+        chat_id,
+        poll_type.as_str()
+    )
+    .fetch_one(pool)
+    .await?
+    .exists
+    .ok_or(Error::SqlxError(sqlx::Error::RowNotFound))
+}
+
+pub async fn poll_is_in_db_by_poll_id(pool: &PgPool, poll_id: String) -> Result<bool, Error> {
+    query!(
+        "SELECT EXISTS(SELECT id from polls WHERE id = $1);",
+        poll_id
+    )
+    .fetch_one(pool)
+    .await?
+    .exists
+    .ok_or(Error::SqlxError(sqlx::Error::RowNotFound))
+}
+
+async fn get_home_spread(pool: &PgPool, game_id: i32) -> Result<Option<f32>, Error> {
+    Ok(
+        query!("SELECT home_spread FROM games WHERE id = $1", game_id)
+            .fetch_optional(pool)
+            .await?
+            .and_then(|row| row.home_spread),
+    )
+}
+
+/// The scraped over/under line or, if there is none, the average points of the games of both teams
+/// this season. The bool is true if the line was scraped.
+async fn get_total(pool: &PgPool, game_id: i32) -> Result<Option<(f32, bool)>, Error> {
+    let row = query!(
+        r#"
+        SELECT
+            games.total AS "scraped_total"
+            ,(SELECT (FLOOR(AVG(finished.home_points + finished.away_points)) + 0.5)::REAL
+            FROM games AS finished, seasons
+            WHERE seasons.is_active = True
+            AND (finished.date_time AT TIME ZONE 'America/New_York')::date >= seasons.regular_season_start
+            AND finished.status IN ('final', 'final_ot')
+            AND (
+                finished.home_team IN (games.home_team, games.away_team)
+                OR finished.away_team IN (games.home_team, games.away_team)
+            )) AS "average_total"
+        FROM games WHERE games.id = $1
+        "#,
+        game_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(
+        row.and_then(|row| match (row.scraped_total, row.average_total) {
+            (Some(total), _) => Some((total, true)),
+            (None, Some(total)) => Some((total, false)),
+            (None, None) => None,
+        }),
+    )
+}
+
+async fn add_poll(
+    pool: &PgPool,
+    poll_id: String,
+    local_id: i32,
+    chat_id: i64,
+    game_id: i32,
+    bet_week_id: i32,
+    home_spread: Option<f32>,
+) -> anyhow::Result<()> {
+    let date_east_coast = east_coast_date_today()?;
+
+    query!(
+        r#"
+        INSERT INTO polls(id,local_id, chat_id, game_id, poll_sent_date, bet_week_id, home_spread, away_srs, home_srs) VALUES 
+        ($1, $2, $3, $4, $5, $6, $7,
+        (SELECT srs_away FROM full_game_information WHERE game_id = $4),
+        (SELECT srs_home FROM full_game_information WHERE game_id = $4));
+        "#,
+        poll_id,
+        local_id,
+        chat_id,
+        game_id,
+        date_east_coast,
+        bet_week_id,
+        home_spread
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn add_total_poll(
+    pool: &PgPool,
+    poll_id: String,
+    local_id: i32,
+    chat_id: i64,
+    game_id: i32,
+    bet_week_id: i32,
+    total: f32,
+) -> anyhow::Result<()> {
+    let date_east_coast = east_coast_date_today()?;
+
+    query!(
+        r#"
+        INSERT INTO polls(id,local_id, chat_id, game_id, poll_sent_date, bet_week_id, poll_type, total) VALUES 
+        ($1, $2, $3, $4, $5, $6, 'total', $7);
+        "#,
+        poll_id,
+        local_id,
+        chat_id,
+        game_id,
+        date_east_coast,
+        bet_week_id,
+        total
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_games(
+    pool: &PgPool,
+    number_of_games: i64,
     start_date: chrono::NaiveDate,
     end_date: chrono::NaiveDate,
 ) -> anyhow::Result<Vec<Game>> {
     let games_raw = query!(
         r#"
+SELECT * FROM (
+  -- the tank battle is listed twice if it's also one of the best games of the week,
+  -- it's kept as the tank battle then
+  SELECT DISTINCT ON (game_id) * FROM (
    (SELECT * FROM (SELECT DISTINCT ON (home_team_id, away_team_id)
                game_id
                ,away_team_id
@@ -326,18 +1135,20 @@ pub async fn get_games(
                ,home_team_id
                ,home_team
                ,srs_sum
-               ,date_time AT TIME ZONE 'EST' as date_time
+               ,date_time
                ,DATE(date_time AT TIME ZONE 'EST') AS date
-               ,to_char(date_time AT TIME ZONE 'EST', 'YYYY-MM-DD') AS date_string
-               ,to_char(date_time AT TIME ZONE 'EST', 'HH:MI AM TZ') AS time_string
-               ,to_char(date_time AT TIME ZONE 'EST', 'YYYY-MM-DD HH:MI AM TZ') AS pretty_date_time
                ,game_quality
+               ,False AS tank_game
  
              FROM public.full_game_information
            WHERE DATE(date_time AT TIME ZONE 'EST') <= $1
            AND DATE(date_time AT TIME ZONE 'EST') >= $2
-           AND (srs_home > 0 OR home_wins > home_losses)
-           AND (srs_away > 0 OR away_wins > away_losses)
+           -- play-in and playoff games are always worth a poll
+           AND (
+               (srs_home > 0 OR home_wins > home_losses)
+               AND (srs_away > 0 OR away_wins > away_losses)
+               OR DATE(date_time AT TIME ZONE 'EST') >= (SELECT play_in_start FROM seasons WHERE is_active = True)
+           )
            --AND ABS(srs_home - srs_away) < 5
            ORDER BY away_team_id, home_team_id, game_id DESC)
  as tmp1
@@ -353,18 +1164,22 @@ pub async fn get_games(
              ,home_team_id 
              ,home_team 
              ,srs_sum 
-             ,date_time AT TIME ZONE 'EST' as date_time 
+             ,date_time
              ,DATE(date_time AT TIME ZONE 'EST') AS date 
-             ,TO_CHAR(date_time AT TIME ZONE 'EST', 'YYYY-MM-DD') AS date_string
-             ,TO_CHAR(date_time AT TIME ZONE 'EST', 'HH:MI AM TZ') AS time_string
-             ,TO_CHAR(date_time AT TIME ZONE 'EST', 'YYYY-MM-DD HH:MI AM TZ') AS pretty_date_time 
              ,game_quality
+             ,True AS tank_game
          FROM public.full_game_information 
          WHERE DATE(date_time AT TIME ZONE 'EST') <= $1 
          AND DATE(date_time AT TIME ZONE 'EST') >= $2 
+         -- nobody tanks in the play-in or the playoffs
+         AND DATE(date_time AT TIME ZONE 'EST') < (SELECT play_in_start FROM seasons WHERE is_active = True)
          ORDER BY ((win_pct_away + win_pct_home)) ASC
          LIMIT 1 
-         ) ORDER BY date_time ASC 
+         )
+  ) AS candidates
+  ORDER BY game_id, tank_game DESC
+) AS games
+ORDER BY date_time ASC, game_id ASC
 
 "#,
         // date a week from now in East Coast time
@@ -384,9 +1199,55 @@ pub async fn get_games(
             home_team_id: record.home_team_id.unwrap(),
             home_team: record.home_team.unwrap(),
             srs_sum: record.srs_sum.unwrap().to_f64().unwrap(),
-            pretty_date_time: record.pretty_date_time.unwrap(),
-            date_string: record.date_string.unwrap(),
-            time_string: record.time_string.unwrap(),
+            date_time: record.date_time.unwrap(),
+            game_quality: record.game_quality.unwrap().to_f64().unwrap(),
+            tank_game: record.tank_game.unwrap(),
+        };
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+pub async fn get_all_games(
+    pool: &PgPool,
+    start_date: chrono::NaiveDate,
+    end_date: chrono::NaiveDate,
+) -> anyhow::Result<Vec<Game>> {
+    let games_raw = query!(
+        r#"
+        SELECT
+            game_id
+            ,away_team_id
+            ,away_team
+            ,home_team_id
+            ,home_team
+            ,srs_sum
+            ,date_time
+            ,game_quality
+        FROM public.full_game_information
+        WHERE DATE(date_time AT TIME ZONE 'EST') <= $1
+        AND DATE(date_time AT TIME ZONE 'EST') >= $2
+        ORDER BY date_time ASC
+        "#,
+        end_date,
+        start_date
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut games = Vec::new();
+    for record in games_raw {
+        let game: Game = Game {
+            id: record.game_id.unwrap(),
+            away_team_id: record.away_team_id.unwrap(),
+            away_team: record.away_team.unwrap(),
+            home_team_id: record.home_team_id.unwrap(),
+            home_team: record.home_team.unwrap(),
+            srs_sum: record.srs_sum.unwrap().to_f64().unwrap(),
+            date_time: record.date_time.unwrap(),
+            game_quality: record.game_quality.unwrap().to_f64().unwrap(),
+            tank_game: false,
         };
         games.push(game);
     }
@@ -394,92 +1255,308 @@ pub async fn get_games(
     Ok(games)
 }
 
-pub fn set_last_updated(current_update: chrono::DateTime<FixedOffset>) -> redis::RedisResult<()> {
-    let client = redis::Client::open("redis://127.0.0.1/")?;
-    let mut con = client.get_connection()?;
+/// Makes sure every team the chat pinned plays in at least one of the games.
+/// For a missing team its best game of the week replaces the worst game that is neither the tank
+/// battle nor a game of another pinned team, so the number of games stays the same.
+pub async fn include_pinned_teams(
+    pool: &PgPool,
+    chat_id: i64,
+    games: &[Game],
+    start_date: chrono::NaiveDate,
+    end_date: chrono::NaiveDate,
+) -> anyhow::Result<Vec<Game>> {
+    let mut games = games.to_vec();
+    let pinned_teams = get_pinned_teams(pool, chat_id).await?;
+    if pinned_teams.is_empty() {
+        return Ok(games);
+    }
+
+    let plays =
+        |game: &Game, team_id: i32| game.away_team_id == team_id || game.home_team_id == team_id;
+    let by_quality = |a: &&Game, b: &&Game| {
+        a.game_quality
+            .partial_cmp(&b.game_quality)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    };
+    let all_games = get_all_games(pool, start_date, end_date).await?;
+
+    for (team_id, _) in pinned_teams.iter() {
+        if games.iter().any(|game| plays(game, *team_id)) {
+            continue;
+        }
+
+        // the team might not play at all this week
+        let best_game = match all_games
+            .iter()
+            .filter(|game| plays(game, *team_id))
+            .max_by(by_quality)
+        {
+            Some(game) => game.clone(),
+            None => continue,
+        };
+
+        let replaced_game = games
+            .iter()
+            .filter(|game| {
+                !game.tank_game
+                    && !pinned_teams
+                        .iter()
+                        .any(|(pinned_team_id, _)| plays(game, *pinned_team_id))
+            })
+            .min_by(by_quality)
+            .map(|game| game.id);
+
+        match replaced_game {
+            Some(replaced_game) => {
+                games.retain(|game| game.id != replaced_game);
+                games.push(best_game);
+            }
+            // every game already features a pinned team, there's nothing left to replace
+            None => break,
+        }
+    }
+
+    games.sort_by_key(|game| game.date_time);
+    Ok(games)
+}
+
+/// Returns id and name of all teams whose name contains the given name, e.g. "celtics"
+pub async fn find_teams(pool: &PgPool, name: &str) -> Result<Vec<(i32, String)>, Error> {
+    let teams = query!(
+        "SELECT id, name FROM teams WHERE name ILIKE '%' || $1 || '%' ORDER BY name",
+        name
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(teams
+        .into_iter()
+        .map(|team| (team.id, team.name.unwrap_or_default()))
+        .collect())
+}
+
+pub async fn get_pinned_teams(pool: &PgPool, chat_id: i64) -> Result<Vec<(i32, String)>, Error> {
+    Ok(query!(
+        r#"
+        SELECT teams.id, teams.name
+        FROM pinned_teams JOIN teams ON teams.id = pinned_teams.team_id
+        WHERE pinned_teams.chat_id = $1
+        ORDER BY teams.name
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|team| (team.id, team.name.unwrap_or_default()))
+    .collect())
+}
+
+pub async fn pin_team(pool: &PgPool, chat_id: i64, team_id: i32) -> Result<(), Error> {
+    query!(
+        "INSERT INTO pinned_teams(chat_id, team_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        chat_id,
+        team_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unpin_team(pool: &PgPool, chat_id: i64, team_id: i32) -> Result<(), Error> {
+    query!(
+        "DELETE FROM pinned_teams WHERE chat_id = $1 AND team_id = $2",
+        chat_id,
+        team_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// How long we wait for Redis before we go on without it
+const REDIS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+static REDIS: OnceCell<ConnectionManager> = OnceCell::new();
+
+pub fn redis_url() -> String {
+    std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string())
+}
+
+/// The connection to the Redis instance at REDIS_URL (redis://127.0.0.1/ by default), shared by
+/// every task of the process. The connection manager reconnects by itself once it's established.
+/// Redis only holds caches, so callers fall back to Postgres if it can't be reached.
+async fn redis_connection() -> redis::RedisResult<ConnectionManager> {
+    if let Some(con) = REDIS.get() {
+        return Ok(con.clone());
+    }
+
+    let client = redis::Client::open(redis_url())?;
+    let con = tokio::time::timeout(REDIS_TIMEOUT, ConnectionManager::new(client))
+        .await
+        .map_err(|_| redis::RedisError::from((redis::ErrorKind::IoError, "Redis timed out")))??;
+
+    Ok(REDIS.get_or_init(|| con).clone())
+}
+
+pub async fn set_last_updated(
+    current_update: chrono::DateTime<FixedOffset>,
+) -> redis::RedisResult<()> {
+    let mut con = redis_connection().await?;
 
     let prev_update = con
         .get("last_updated".to_string())
+        .await
         .unwrap_or_else(|_| "2000-01-01T01:00:00-05:00".to_string());
 
     let prev_update = chrono::DateTime::parse_from_rfc3339(&prev_update).unwrap();
     if prev_update < current_update {
-        let _: () = con.set("last_updated", current_update.to_rfc3339())?;
+        let _: () = con.set("last_updated", current_update.to_rfc3339()).await?;
     }
 
     Ok(())
 }
 
-pub fn get_duration_since_update() -> redis::RedisResult<String> {
-    let client = redis::Client::open("redis://127.0.0.1/")?;
-    let mut con = client.get_connection()?;
+/// The last update of the live scores from Redis, or the last successful live scrape if Redis is
+/// unavailable
+async fn get_last_updated(pool: &PgPool) -> Result<chrono::DateTime<Utc>, Error> {
+    if let Ok(mut con) = redis_connection().await {
+        if let Ok(last_updated) = con.get::<_, String>("last_updated").await {
+            return Ok(chrono::DateTime::parse_from_rfc3339(&last_updated)?.with_timezone(&Utc));
+        }
+    }
+
+    let last_run =
+        query!("SELECT last_run FROM job_runs WHERE name = 'live_scores' AND succeeded = True")
+            .fetch_optional(pool)
+            .await?
+            .map(|row| row.last_run)
+            .unwrap_or_else(|| Utc.ymd(2000, 1, 1).and_hms(6, 0, 0));
 
-    let last_updated = con
-        .get("last_updated".to_string())
-        .unwrap_or_else(|_| "2000-01-01T01:00:00-05:00".to_string());
-    let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated).unwrap();
+    Ok(last_run)
+}
 
-    let time_since_update = chrono::Utc::now() - last_updated.with_timezone(&chrono::Utc);
-    dbg!(chrono::Utc::now().with_timezone(&chrono::Utc));
-    dbg!(last_updated.with_timezone(&chrono::Utc));
-    dbg!(time_since_update.num_minutes());
+pub async fn get_duration_since_update(pool: &PgPool, time_zone: Tz) -> Result<String, Error> {
+    let last_updated = get_last_updated(pool).await?;
+
+    let time_since_update = chrono::Utc::now() - last_updated;
+    let last_updated = local_time(last_updated, time_zone);
     match time_since_update.num_minutes() {
         0..=59 => Ok(format!(
-            "Last Update: {minutes}min ago",
+            "Last Update: {last_updated} ({minutes}min ago)",
+            last_updated = last_updated,
             minutes = time_since_update.num_minutes()
         )),
-        60..=119 => Ok("Last Update: 1 hour ago".to_string()),
+        60..=119 => Ok(format!(
+            "Last Update: {last_updated} (1 hour ago)",
+            last_updated = last_updated
+        )),
         _ => Ok(format!(
-            "Last Update: {hours} hours ago",
+            "Last Update: {last_updated} ({hours} hours ago)",
+            last_updated = last_updated,
             hours = time_since_update.num_hours()
         )),
     }
 }
 
-pub fn cache_games(games: Vec<Game>) -> redis::RedisResult<()> {
-    let client = redis::Client::open("redis://127.0.0.1/")?;
-    let mut con = client.get_connection()?;
-    for (game_number, game) in games.into_iter().enumerate() {
-        let _: () = con.hset_multiple(
-            game_number,
-            &[
-                ("id", game.id.to_string()),
-                ("away_team_id", game.away_team_id.to_string()),
-                ("away_team", game.away_team.to_owned()),
-                ("home_team_id", game.home_team_id.to_string()),
-                ("home_team", game.home_team.to_owned()),
-                ("srs_sum", game.srs_sum.to_string()),
-                ("pretty_date_time", game.pretty_date_time.to_owned()),
-                ("date_string", game.date_string.to_owned()),
-                ("time_string", game.time_string.to_owned()),
-            ],
-        )?;
-        let _: () = con.expire(game_number, 60 * 60 * 24)?;
-    }
-    Ok(())
+/// Bump it whenever Game changes, so old caches are rejected instead of misread
+const GAME_CACHE_VERSION: u32 = 1;
+
+/// The games of one bet week for one game mode, as they are stored in Redis
+#[derive(Serialize, Deserialize)]
+struct CachedGames {
+    version: u32,
+    start_date: chrono::NaiveDate,
+    end_date: chrono::NaiveDate,
+    game_mode: String,
+    length: usize,
+    games: Vec<Game>,
 }
 
-pub fn cache_to_games() -> redis::RedisResult<Vec<Game>> {
-    let client = redis::Client::open("redis://127.0.0.1/")?;
-    let mut con = client.get_connection()?;
+/// e.g. "bbb:games:2021-01-10:best_of_10", the bet week is identified by its first day
+fn game_cache_key(game_mode: GameMode, start_date: chrono::NaiveDate) -> String {
+    format!("bbb:games:{}:{}", start_date, game_mode.key())
+}
 
-    let mut games: Vec<Game> = Vec::new();
+fn game_cache_error(description: &'static str) -> redis::RedisError {
+    (redis::ErrorKind::TypeError, description).into()
+}
 
-    for game_number in 0..=10 {
-        let game = Game {
-            id: con.hget(game_number, "id")?,
-            away_team_id: con.hget(game_number, "away_team_id")?,
-            away_team: con.hget(game_number, "away_team")?,
-            home_team_id: con.hget(game_number, "home_team_id")?,
-            home_team: con.hget(game_number, "home_team")?,
-            srs_sum: con.hget(game_number, "srs_sum")?,
-            pretty_date_time: con.hget(game_number, "pretty_date_time")?,
-            date_string: con.hget(game_number, "date_string")?,
-            time_string: con.hget(game_number, "time_string")?,
-        };
-        games.push(game);
+pub async fn cache_games(
+    games: &[Game],
+    game_mode: GameMode,
+    start_date: chrono::NaiveDate,
+    end_date: chrono::NaiveDate,
+) -> redis::RedisResult<()> {
+    let mut con = redis_connection().await?;
+    let cached_games = serde_json::to_string(&CachedGames {
+        version: GAME_CACHE_VERSION,
+        start_date,
+        end_date,
+        game_mode: game_mode.key(),
+        length: games.len(),
+        games: games.to_vec(),
+    })
+    .map_err(|_| game_cache_error("Could not serialize games"))?;
+
+    let _: () = con
+        .set_ex(
+            game_cache_key(game_mode, start_date),
+            cached_games,
+            60 * 60 * 24,
+        )
+        .await?;
+    Ok(())
+}
+
+/// The cached games of the bet week, anything that doesn't match the bet week and the game mode
+/// exactly is rejected, so the caller gets the games from Postgres instead
+pub async fn cache_to_games(
+    game_mode: GameMode,
+    start_date: chrono::NaiveDate,
+    end_date: chrono::NaiveDate,
+) -> redis::RedisResult<Vec<Game>> {
+    let mut con = redis_connection().await?;
+
+    let cached_games: Option<String> = con.get(game_cache_key(game_mode, start_date)).await?;
+    let cached_games: CachedGames =
+        serde_json::from_str(&cached_games.ok_or_else(|| game_cache_error("No games cached"))?)
+            .map_err(|_| game_cache_error("Could not deserialize games"))?;
+
+    if cached_games.version != GAME_CACHE_VERSION
+        || cached_games.start_date != start_date
+        || cached_games.end_date != end_date
+        || cached_games.game_mode != game_mode.key()
+        || cached_games.length != cached_games.games.len()
+    {
+        return Err(game_cache_error("Cached games don't match the bet week"));
     }
-    Ok(games)
+
+    Ok(cached_games.games)
+}
+
+/// If any game is being played right now.
+/// Games that tipped off but weren't picked up by the live scraper yet count as well, games take
+/// about two and a half hours, overtimes and long reviews included we give them four.
+pub async fn games_in_progress(pool: &PgPool) -> Result<bool, Error> {
+    query!(
+        r#"
+        SELECT EXISTS(
+            SELECT *
+            FROM games
+            WHERE status = 'in_progress'
+            OR (status = 'scheduled'
+                AND date_time <= NOW()
+                AND date_time > NOW() - INTERVAL '4 HOURS')
+        ) AS exists
+        "#
+    )
+    .fetch_one(pool)
+    .await?
+    .exists
+    .ok_or(Error::SqlxError(sqlx::Error::RowNotFound))
 }
 
 async fn polls_exist(pool: &PgPool) -> Result<bool, Error> {
@@ -496,9 +1573,10 @@ pub async fn stop_poll(pool: &PgPool, bot: &teloxide::Bot) -> Result<(), Error>
 
     let polls_to_close = query!(
         r#"
-        SELECT id, local_id, chat_id FROM polls
-       WHERE game_id IN
+        SELECT id, local_id, chat_id, is_keyboard FROM polls
+       WHERE (game_id IN
        (SELECT id FROM games WHERE now() at time zone 'EST' >= date_time AT TIME ZONE 'EST')
+       OR is_void = True)
        AND is_open = True;
         "#
     )
@@ -508,6 +1586,21 @@ pub async fn stop_poll(pool: &PgPool, bot: &teloxide::Bot) -> Result<(), Error>
     for poll in polls_to_close {
         let chat_id = poll.chat_id.unwrap_or(-1);
         dbg!("Closing Poll:", &poll, chat_id);
+        // games sent with keyboard bets lose their buttons and get a lock
+        if poll.is_keyboard {
+            query!(
+                r#"
+        UPDATE polls SET is_open = False WHERE id = $1
+        "#,
+                poll.id
+            )
+            .execute(pool)
+            .await?;
+            if let Err(e) = update_keyboard_game(pool, bot, poll.id.clone()).await {
+                log::error!("keyboard game {} could not be closed: {}", poll.id, e);
+            }
+            continue;
+        }
         match bot.stop_poll(chat_id, poll.local_id.unwrap()).send().await {
             Ok(_)
             | Err(RequestError::ApiError {
@@ -525,7 +1618,12 @@ pub async fn stop_poll(pool: &PgPool, bot: &teloxide::Bot) -> Result<(), Error>
                 continue;
             }
             Err(e) => {
-                dbg!(e);
+                log::error!(
+                    "poll {} in chat {} could not be stopped: {}",
+                    poll.id,
+                    chat_id,
+                    e
+                );
                 continue;
             }
         }
@@ -534,49 +1632,562 @@ pub async fn stop_poll(pool: &PgPool, bot: &teloxide::Bot) -> Result<(), Error>
     Ok(())
 }
 
+/// Sends a poll for every playoff series of the active season that has a matchup but didn't
+/// start yet and wasn't sent to the chat before. The options are the winner and the number of
+/// games, e.g. "Milwaukee Bucks in 6".
+pub async fn send_series_polls(
+    pool: &PgPool,
+    chat_id: i64,
+    bot: &teloxide::Bot,
+) -> anyhow::Result<()> {
+    let upcoming_series = query!(
+        r#"
+        SELECT playoff_series.id, playoff_series.round, t1.name AS team_1, t2.name AS team_2
+        FROM playoff_series
+        JOIN seasons ON seasons.id = playoff_series.season_id
+        JOIN teams AS t1 ON t1.id = playoff_series.team_1
+        JOIN teams AS t2 ON t2.id = playoff_series.team_2
+        WHERE seasons.is_active = True
+        AND playoff_series.team_1_wins + playoff_series.team_2_wins = 0
+        AND NOT EXISTS(
+            SELECT * FROM series_polls
+            WHERE series_polls.series_id = playoff_series.id
+            AND series_polls.chat_id = $1
+        )
+        AND NOT EXISTS(
+            SELECT * FROM games
+            WHERE (games.date_time AT TIME ZONE 'America/New_York')::date >= seasons.playoffs_start
+            AND games.date_time <= NOW()
+            AND (
+                (games.away_team = playoff_series.team_1 AND games.home_team = playoff_series.team_2)
+                OR (games.away_team = playoff_series.team_2 AND games.home_team = playoff_series.team_1)
+            )
+        )
+        ORDER BY playoff_series.round ASC, playoff_series.id ASC
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for series in upcoming_series {
+        let team_1 = series.team_1;
+        let team_2 = series.team_2;
+        // option 0 to 3: team_1 in 4 to 7, option 4 to 7: team_2 in 4 to 7
+        let options = [&team_1, &team_2]
+            .iter()
+            .flat_map(|team| (4..=7).map(move |games| format!("{} in {}", team, games)))
+            .collect::<Vec<_>>();
+
+        let poll = bot
+            .send_poll(
+                chat_id,
+                format!(
+                    "Playoffs - {round}\n{team_1} vs. {team_2}\nWho wins the series in how many games?",
+                    round = playoff_round_name(series.round),
+                    team_1 = team_1,
+                    team_2 = team_2
+                ),
+                options,
+            )
+            .disable_notification(true)
+            .is_anonymous(false)
+            .send()
+            .await;
+
+        match poll {
+            Ok(poll) => {
+                let poll_id = poll.poll().expect("").id.to_owned();
+                query!(
+                    "INSERT INTO series_polls(id, local_id, chat_id, series_id) VALUES ($1, $2, $3, $4)",
+                    poll_id,
+                    poll.id,
+                    chat_id,
+                    series.id
+                )
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => {
+                log::error!(
+                    "series poll of series {} could not be sent to chat {}: {}",
+                    series.id,
+                    chat_id,
+                    e
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Closes the series polls of every series whose first game tipped off
+pub async fn stop_series_polls(pool: &PgPool, bot: &teloxide::Bot) -> Result<(), Error> {
+    let polls_to_close = query!(
+        r#"
+        SELECT series_polls.id, series_polls.local_id, series_polls.chat_id
+        FROM series_polls
+        JOIN playoff_series ON playoff_series.id = series_polls.series_id
+        JOIN seasons ON seasons.id = playoff_series.season_id
+        WHERE series_polls.is_open = True
+        AND (
+            playoff_series.team_1_wins + playoff_series.team_2_wins > 0
+            OR EXISTS(
+                SELECT * FROM games
+                WHERE (games.date_time AT TIME ZONE 'America/New_York')::date >= seasons.playoffs_start
+                AND games.date_time <= NOW()
+                AND (
+                    (games.away_team = playoff_series.team_1 AND games.home_team = playoff_series.team_2)
+                    OR (games.away_team = playoff_series.team_2 AND games.home_team = playoff_series.team_1)
+                )
+            )
+        )
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for poll in polls_to_close {
+        let chat_id = poll.chat_id.unwrap_or(-1);
+        match bot
+            .stop_poll(chat_id, poll.local_id.unwrap_or_default())
+            .send()
+            .await
+        {
+            Ok(_)
+            | Err(RequestError::ApiError {
+                kind: teloxide::ApiErrorKind::Known(KnownApiErrorKind::ChatNotFound),
+                ..
+            }) => {
+                query!(
+                    "UPDATE series_polls SET is_open = False WHERE id = $1",
+                    poll.id
+                )
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => {
+                log::error!(
+                    "series poll {} in chat {} could not be stopped: {}",
+                    poll.id,
+                    chat_id,
+                    e
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn series_poll_is_in_db(pool: &PgPool, poll_id: String) -> Result<bool, Error> {
+    query!(
+        "SELECT EXISTS(SELECT id from series_polls WHERE id = $1);",
+        poll_id
+    )
+    .fetch_one(pool)
+    .await?
+    .exists
+    .ok_or(Error::SqlxError(sqlx::Error::RowNotFound))
+}
+
+/// option: the index of the picked option of the series poll, see send_series_polls
+pub async fn add_series_bet(
+    pool: &PgPool,
+    poll_id: String,
+    user_id: i64,
+    option: i32,
+) -> Result<(), Error> {
+    query!(
+        r#"
+        INSERT INTO series_bets(chat_id, user_id, poll_id, series_id, winner, number_of_games)
+        SELECT
+            series_polls.chat_id
+            ,$2
+            ,series_polls.id
+            ,series_polls.series_id
+            ,CASE WHEN $3 < 4 THEN playoff_series.team_1 ELSE playoff_series.team_2 END
+            ,4 + $3 % 4
+        FROM series_polls
+        JOIN playoff_series ON playoff_series.id = series_polls.series_id
+        WHERE series_polls.id = $1
+        ON CONFLICT DO NOTHING;
+        "#,
+        poll_id,
+        user_id,
+        option
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// The series of the active season round by round, followed by the bracket standings of the chat
+pub async fn show_bracket_standings(
+    cx: &UpdateWithCx<Message>,
+    pool: &PgPool,
+    chat_id: i64,
+) -> Result<(), Error> {
+    let all_series = query!(
+        r#"
+        SELECT playoff_series.round, t1.name AS team_1, playoff_series.team_1_wins, t2.name AS team_2, playoff_series.team_2_wins
+        FROM playoff_series
+        JOIN seasons ON seasons.id = playoff_series.season_id
+        JOIN teams AS t1 ON t1.id = playoff_series.team_1
+        JOIN teams AS t2 ON t2.id = playoff_series.team_2
+        WHERE seasons.is_active = True
+        ORDER BY playoff_series.round ASC, playoff_series.id ASC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if all_series.is_empty() {
+        cx.answer_str("The playoffs didn't start yet!").await?;
+        return Ok(());
+    }
+
+    let mut bracket = String::from("Playoff Bracket\n");
+    let mut round = 0;
+    for series in all_series {
+        if series.round != round {
+            round = series.round;
+            bracket.push_str(&format!(
+                "\n{} ({} points per series)\n",
+                playoff_round_name(round),
+                1 << (round - 1)
+            ));
+        }
+        bracket.push_str(&format!(
+            "{} {}-{} {}\n",
+            series.team_1, series.team_1_wins, series.team_2_wins, series.team_2
+        ));
+    }
+
+    let ranking_query = query!(
+        r#"
+        SELECT
+            users.first_name
+            ,SUM(CASE WHEN bracket_points.round = 1 THEN bracket_points.points ELSE 0 END) AS round_1
+            ,SUM(CASE WHEN bracket_points.round = 2 THEN bracket_points.points ELSE 0 END) AS round_2
+            ,SUM(CASE WHEN bracket_points.round = 3 THEN bracket_points.points ELSE 0 END) AS round_3
+            ,SUM(CASE WHEN bracket_points.round = 4 THEN bracket_points.points ELSE 0 END) AS round_4
+            ,SUM(bracket_points.points) AS total
+            ,RANK() OVER (ORDER BY SUM(bracket_points.points) DESC)
+        FROM bracket_points
+        JOIN users ON users.id = bracket_points.user_id
+        JOIN seasons ON seasons.id = bracket_points.season_id
+        WHERE seasons.is_active = True
+        AND bracket_points.chat_id = $1
+        GROUP BY users.id, users.first_name
+        ORDER BY total DESC
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    bracket.push_str("\n\nRank |          Name          | R1 | R2 | CF | F | Total\n--- --- --- --- --- --- --- --- --- --- ---\n");
+    if ranking_query.is_empty() {
+        bracket.push_str("You can see the standings once the first series is over.\n");
+    }
+    for record in ranking_query {
+        bracket.push_str(&format!(
+            "    {rank}    | {name} | {round_1} | {round_2} | {round_3} | {round_4} | {total}\n",
+            rank = record.rank.unwrap_or(-1),
+            name = name_cell(record.first_name),
+            round_1 = record.round_1.unwrap_or(0),
+            round_2 = record.round_2.unwrap_or(0),
+            round_3 = record.round_3.unwrap_or(0),
+            round_4 = record.round_4.unwrap_or(0),
+            total = record.total.unwrap_or(0)
+        ));
+    }
+
+    cx.answer(&bracket).send().await?;
+
+    Ok(())
+}
+
+/// Called after a game moved to a new date. Polls for the game whose bet week is over before the
+/// game is played are voided, every chat with a poll for the game is told about the new date.
+/// A tip-off that only moved within the same day isn't announced.
+pub async fn handle_rescheduled_game(
+    pool: &PgPool,
+    bot: &teloxide::Bot,
+    game_id: i32,
+    old_date_time: chrono::DateTime<Utc>,
+) -> Result<(), Error> {
+    // bet weeks are in east coast dates, so is the date of the game
+    let polls = query!(
+        r#"
+        UPDATE polls SET is_void = (
+            (games.date_time AT TIME ZONE 'America/New_York')::date
+            NOT BETWEEN bet_weeks.start_date AND bet_weeks.end_date
+        )
+        FROM games, bet_weeks
+        WHERE games.id = polls.game_id
+        AND bet_weeks.id = polls.bet_week_id
+        AND polls.game_id = $1
+        RETURNING polls.chat_id, polls.is_void, bet_weeks.week_number, games.date_time
+        "#,
+        game_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let new_date_time = match polls.get(0).and_then(|poll| poll.date_time) {
+        Some(new_date_time) => new_date_time,
+        None => return Ok(()),
+    };
+    // bet weeks are in east coast dates, the time of day doesn't change which one the game is in
+    let east_coast_date = |date_time: chrono::DateTime<Utc>| {
+        date_time
+            .with_timezone(&chrono_tz::America::New_York)
+            .date()
+            .naive_local()
+    };
+    if east_coast_date(new_date_time) == east_coast_date(old_date_time) {
+        return Ok(());
+    }
+
+    let game = query!(
+        "SELECT away_team, home_team FROM full_game_information WHERE game_id = $1",
+        game_id
+    )
+    .fetch_one(pool)
+    .await?;
+    let away_team = game.away_team.unwrap_or_default();
+    let home_team = game.home_team.unwrap_or_default();
+
+    // chats with totals polls have two polls for the game, but only need to be told once
+    let mut notified_chats = Vec::new();
+    for poll in polls {
+        let chat_id = poll.chat_id.unwrap_or(-1);
+        if notified_chats.contains(&chat_id) {
+            continue;
+        }
+        notified_chats.push(chat_id);
+        let time_zone = get_time_zone(pool, chat_id).await?;
+
+        let mut message = format!(
+            "{away_team} @ {home_team} was rescheduled!
+From: {old_date} {old_time}
+To: {new_date} {new_time}
+
+",
+            away_team = away_team,
+            home_team = home_team,
+            old_date = local_date(old_date_time, time_zone),
+            old_time = local_time(old_date_time, time_zone),
+            new_date = local_date(new_date_time, time_zone),
+            new_time = local_time(new_date_time, time_zone),
+        );
+        match poll.is_void {
+            true => message.push_str(&format!(
+                "The game is played after week {} is over, so bets on it don't count anymore.",
+                poll.week_number.unwrap_or(-1)
+            )),
+            false => message.push_str("Your bets on the game still count."),
+        }
+
+        if let Err(e) = bot.send_message(chat_id, message).send().await {
+            log::error!(
+                "could not tell chat {} about the rescheduled game: {}",
+                chat_id,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn show_all_bets_season(
     pool: &PgPool,
     cx: &UpdateWithCx<Message>,
     chat_id: i64,
 ) -> Result<(), Error> {
-    let ranking_query = query!(
+    let rows = query!(
         "SELECT * from correct_bets_season WHERE chat_id = $1 ORDER BY rank_number ASC",
         chat_id
     )
     .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|record| StandingsRow {
+        user_id: record.user_id.unwrap_or(-1),
+        rank: record.rank_number.unwrap_or(-1),
+        first_name: record.first_name,
+        points: format!(
+            "{}/{}",
+            record.correct_bets_total.unwrap_or(-1),
+            record.finished_games.unwrap_or(-1)
+        ),
+    })
+    .collect();
+
+    let totals = totals_column(pool, chat_id, None).await?;
+    let rankings = standings_message(
+        pool,
+        chat_id,
+        "Fraction of correct bets for the whole season\n(including the ongoing week)\n\n",
+        "Correct Bets",
+        rows,
+        totals,
+    )
     .await?;
 
-    let mut rankings = String::from("Fraction of correct bets for the whole season\n(including the ongoing week)\n\nRank |          Name          |    Correct Bets\n--- --- --- --- --- --- --- --- --- --- ---\n",
-            );
+    cx.answer(&rankings).send().await?;
 
-    for record in ranking_query {
-        let first_name = record.first_name.unwrap_or_else(|| "X".to_string());
-        let mut spacing = String::from("");
+    Ok(())
+}
 
-        if let len @ 0..=13 = first_name.len() {
-            for _ in 0..(13 - len) {
-                spacing.push('\t')
-            }
+/// A row of a standings table, points is the text of the points column, e.g. "7/10"
+struct StandingsRow {
+    user_id: i64,
+    rank: i64,
+    first_name: Option<String>,
+    points: String,
+}
+
+/// The first name padded with tabs, so the columns of a table roughly line up
+fn name_cell(first_name: Option<String>) -> String {
+    let first_name = first_name.unwrap_or_else(|| "X".to_string());
+    let mut spacing = String::from("");
+
+    if let len @ 0..=13 = first_name.len() {
+        for _ in 0..(13 - len) {
+            spacing.push('\t')
         }
+    }
+
+    format!(
+        "{spacing} {first_name} {spacing}",
+        spacing = spacing,
+        first_name = first_name
+    )
+}
+
+/// Every standings table looks the same: the title, a row per user with the points column and
+/// optionally the totals column, and the time since the last update of the scores below it.
+async fn standings_message(
+    pool: &PgPool,
+    chat_id: i64,
+    title: &str,
+    points_header: &str,
+    rows: Vec<StandingsRow>,
+    totals: Option<TotalsColumn>,
+) -> Result<String, Error> {
+    let mut rankings = format!(
+        "{title}Rank |          Name          |    {points_header}{totals_header}\n--- --- --- --- --- --- --- --- --- --- ---\n",
+        title = title,
+        points_header = points_header,
+        totals_header = totals.as_ref().map(|_| " | Totals").unwrap_or_default()
+    );
+
+    for row in rows {
         rankings.push_str(
             &format!(
-                "    {rank}    | {spacing} {first_name} {spacing} | \t\t\t\t\t\t{correct_bets_total}/{finished_games}\n",
-                rank = record.rank_number.unwrap_or(-1),
-                first_name = first_name,
-                spacing = spacing,
-                finished_games = record.finished_games.unwrap_or(-1),
-                correct_bets_total = record.correct_bets_total.unwrap_or(-1)
+                "    {rank}    | {name} | \t\t\t\t\t\t{points}{totals}\n",
+                rank = row.rank,
+                name = name_cell(row.first_name),
+                points = row.points,
+                totals = totals
+                    .as_ref()
+                    .map(|totals| totals.cell(row.user_id))
+                    .unwrap_or_default()
             )
             .as_str(),
         );
     }
 
+    let time_zone = get_time_zone(pool, chat_id).await?;
     rankings.push('\n');
-    rankings.push_str(&get_duration_since_update().unwrap_or_default());
+    rankings.push_str(
+        &get_duration_since_update(pool, time_zone)
+            .await
+            .unwrap_or_default(),
+    );
 
-    cx.answer(&rankings).send().await?;
+    Ok(rankings)
+}
 
-    Ok(())
+/// Correct totals bets of every user of a chat, shown next to the standings of the winner bets
+struct TotalsColumn {
+    correct_bets: std::collections::HashMap<i64, i64>,
+    finished_polls: i64,
+}
+
+impl TotalsColumn {
+    fn cell(&self, user_id: i64) -> String {
+        format!(
+            " | {}/{}",
+            self.correct_bets.get(&user_id).copied().unwrap_or(0),
+            self.finished_polls
+        )
+    }
+}
+
+/// None if the chat doesn't get totals polls.
+/// week_number: the week to count the totals bets of, None for the whole season
+async fn totals_column(
+    pool: &PgPool,
+    chat_id: i64,
+    week_number: Option<i32>,
+) -> Result<Option<TotalsColumn>, Error> {
+    if !get_totals_polls(pool, chat_id).await? {
+        return Ok(None);
+    }
+
+    let correct_bets = query!(
+        r#"
+        SELECT user_id, COUNT(*) AS correct_total_bets
+        FROM correct_total_bets
+        WHERE chat_id = $1
+        AND ($2::INT IS NULL OR week_number = $2)
+        GROUP BY user_id
+        "#,
+        chat_id,
+        week_number
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        (
+            row.user_id.unwrap_or(-1),
+            row.correct_total_bets.unwrap_or(0),
+        )
+    })
+    .collect();
+
+    let finished_polls = query!(
+        r#"
+        SELECT COUNT(*) AS finished_polls
+        FROM polls
+        JOIN games ON games.id = polls.game_id
+        JOIN bet_weeks ON bet_weeks.id = polls.bet_week_id
+        WHERE polls.chat_id = $1
+        AND ($2::INT IS NULL OR bet_weeks.week_number = $2)
+        AND polls.poll_type = 'total'
+        AND polls.is_void = False
+        AND games.status IN ('final', 'final_ot')
+        "#,
+        chat_id,
+        week_number
+    )
+    .fetch_one(pool)
+    .await?
+    .finished_polls
+    .unwrap_or(0);
+
+    Ok(Some(TotalsColumn {
+        correct_bets,
+        finished_polls,
+    }))
 }
 
 pub async fn number_of_finished_games_week(
@@ -592,8 +2203,9 @@ pub async fn number_of_finished_games_week(
             polls JOIN games ON games.id = polls.game_id
             JOIN bet_weeks ON bet_weeks.id = polls.bet_week_id
         WHERE
-            home_points > 0
-            AND away_points > 0
+            games.status IN ('final', 'final_ot')
+            AND polls.is_void = False
+            AND polls.poll_type = 'winner'
             AND bet_weeks.week_number = $1
             AND polls.chat_id = $2;
         "#,
@@ -625,6 +2237,15 @@ pub async fn user_is_admin(chat_id: i64, cx: &UpdateWithCx<Message>) -> Result<b
 }
 
 pub async fn remove_chat(pool: &PgPool, chat_id: i64) -> Result<(), Error> {
+    query!("DELETE FROM series_bets WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
+    query!("DELETE FROM series_polls WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
+    query!("DELETE FROM confidence_picks WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
     query!("DELETE FROM bets WHERE chat_id = $1", chat_id)
         .execute(pool)
         .await?;
@@ -634,6 +2255,9 @@ pub async fn remove_chat(pool: &PgPool, chat_id: i64) -> Result<(), Error> {
     query!("DELETE FROM bet_weeks WHERE chat_id = $1", chat_id)
         .execute(pool)
         .await?;
+    query!("DELETE FROM pinned_teams WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
     query!("DELETE FROM chats WHERE id = $1", chat_id)
         .execute(pool)
         .await?;
@@ -692,6 +2316,111 @@ pub async fn add_bet(
     Ok(())
 }
 
+/// Stores a click on a button of a game sent with keyboard bets, option 0 is the away team and
+/// option 1 the home team. Until the game starts users can change their pick.
+pub async fn add_keyboard_bet(
+    pool: &PgPool,
+    poll_id: String,
+    user_id: i64,
+    option: i32,
+) -> Result<KeyboardBet, Error> {
+    let mut tx = pool.begin().await?;
+
+    // locking the poll makes clicks on the same game wait for each other, so nobody ends up with
+    // both teams
+    let poll = query!(
+        r#"
+        SELECT
+            polls.chat_id
+            ,polls.game_id
+            ,games.away_team
+            ,games.home_team
+            ,polls.is_open = True AND polls.is_void = False AND games.date_time > NOW() AS accepts_bets
+        FROM polls
+        JOIN games ON games.id = polls.game_id
+        WHERE polls.id = $1
+        AND polls.is_keyboard = True
+        FOR UPDATE OF polls
+        "#,
+        poll_id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let poll = match poll {
+        Some(poll) if poll.accepts_bets.unwrap_or(false) => poll,
+        _ => return Ok(KeyboardBet::Closed),
+    };
+    let bet = match option {
+        0 => poll.away_team,
+        _ => poll.home_team,
+    };
+
+    query!(
+        r#"
+        DELETE FROM bets
+        WHERE poll_id = $1
+        AND user_id = $2
+        AND bet_type = 'winner'
+        AND bet <> $3
+        "#,
+        poll_id,
+        user_id,
+        bet
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let inserted = query!(
+        r#"
+        INSERT INTO bets(game_id, chat_id, user_id, bet, poll_id) VALUES 
+        ($1, $2, $3, $4, $5)
+        ON CONFLICT DO NOTHING;
+        "#,
+        poll.game_id,
+        poll.chat_id,
+        user_id,
+        bet,
+        poll_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(match inserted.rows_affected() {
+        0 => KeyboardBet::Unchanged,
+        _ => KeyboardBet::Added,
+    })
+}
+
+/// over: the first option (over) was picked, otherwise the under
+pub async fn add_total_bet(
+    pool: &PgPool,
+    game_id: i32,
+    chat_id: i64,
+    user_id: i64,
+    over: bool,
+    poll_id: String,
+) -> Result<(), Error> {
+    query!(
+        r#"
+        INSERT INTO bets(game_id, chat_id, user_id, bet_type, over, poll_id) VALUES 
+        ($1, $2, $3, 'total', $4, $5)
+        ON CONFLICT DO NOTHING;
+        "#,
+        game_id,
+        chat_id,
+        user_id,
+        over,
+        poll_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn bet_to_team_id(pool: &PgPool, bet: i32, game_id: i32) -> Result<i32, Error> {
     // bet is 0 if first option was picked (the away team)
     // bet is 1 if second option was picked (the home team)
@@ -720,6 +2449,17 @@ pub async fn bet_to_team_id(pool: &PgPool, bet: i32, game_id: i32) -> Result<i32
     }
 }
 
+pub async fn get_poll_type(pool: &PgPool, poll_id: String) -> Result<PollType, Error> {
+    let row = query!("SELECT poll_type FROM polls WHERE id = $1", poll_id)
+        .fetch_one(pool)
+        .await?;
+
+    match row.poll_type.as_str() {
+        "total" => Ok(PollType::Total),
+        _ => Ok(PollType::Winner),
+    }
+}
+
 pub async fn get_chat_id_game_id_from_poll(
     pool: &PgPool,
     poll_id: String,
@@ -771,64 +2511,292 @@ pub async fn add_user(
     Ok(())
 }
 
-pub async fn show_complete_rankings(
+pub async fn show_complete_rankings(
+    cx: &UpdateWithCx<Message>,
+    pool: &PgPool,
+    chat_id: i64,
+) -> Result<(), Error> {
+    let rows = query!(
+        r#"
+        SELECT 
+         id
+         ,first_name
+         ,last_name
+         ,username
+         ,chat_id
+         ,SUM(CASE WHEN rank_number = 1 THEN 1 ELSE 0 END)  as weeks_won
+         ,RANK() OVER (partition by chat_id ORDER BY SUM(CASE WHEN rank_number = 1 THEN 1 ELSE 0 END) DESC )
+        FROM weekly_rankings WHERE chat_id = $1 
+    GROUP BY
+    id
+    ,first_name
+    ,last_name
+    ,username
+    ,chat_id
+    ORDER BY weeks_won DESC;
+        "#,
+        chat_id
+
+
+    ).fetch_all(pool).await?
+    .into_iter()
+    .map(|record| StandingsRow {
+        user_id: record.id.unwrap_or(-1),
+        rank: record.rank.unwrap_or(-1),
+        first_name: record.first_name,
+        points: record.weeks_won.unwrap_or(-1).to_string(),
+    })
+    .collect();
+
+    let rankings = standings_message(
+        pool,
+        chat_id,
+        "Standings (including current week)\n\n",
+        "Weeks Won",
+        rows,
+        None,
+    )
+    .await?;
+
+    cx.answer(&rankings).send().await?;
+
+    Ok(())
+}
+
+pub async fn show_confidence_rankings(
+    cx: &UpdateWithCx<Message>,
+    pool: &PgPool,
+    chat_id: i64,
+) -> Result<(), Error> {
+    let rows = query!(
+        r#"
+        SELECT
+         id
+         ,first_name
+         ,SUM(confidence_points_week) AS confidence_points
+         ,RANK() OVER (ORDER BY SUM(confidence_points_week) DESC)
+        FROM weekly_confidence_rankings WHERE chat_id = $1
+        GROUP BY id, first_name
+        ORDER BY confidence_points DESC;
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|record| StandingsRow {
+        user_id: record.id.unwrap_or(-1),
+        rank: record.rank.unwrap_or(-1),
+        first_name: record.first_name,
+        points: record.confidence_points.unwrap_or(-1).to_string(),
+    })
+    .collect();
+
+    let rankings = standings_message(
+        pool,
+        chat_id,
+        "Confidence points for the whole season\n(including the ongoing week)\n\n",
+        "Points",
+        rows,
+        None,
+    )
+    .await?;
+
+    cx.answer(&rankings).send().await?;
+
+    Ok(())
+}
+
+pub async fn show_weighted_rankings(
+    cx: &UpdateWithCx<Message>,
+    pool: &PgPool,
+    chat_id: i64,
+) -> Result<(), Error> {
+    let rows = query!(
+        r#"
+        SELECT
+         id
+         ,first_name
+         ,SUM(weighted_points_week) AS weighted_points
+         ,RANK() OVER (ORDER BY SUM(weighted_points_week) DESC)
+        FROM weekly_weighted_rankings WHERE chat_id = $1
+        GROUP BY id, first_name
+        ORDER BY weighted_points DESC;
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|record| StandingsRow {
+        user_id: record.id.unwrap_or(-1),
+        rank: record.rank.unwrap_or(-1),
+        first_name: record.first_name,
+        points: record
+            .weighted_points
+            .map(|points| points.to_string())
+            .unwrap_or_default(),
+    })
+    .collect();
+
+    let rankings = standings_message(
+        pool,
+        chat_id,
+        "Points with upset bonus for the whole season\n(including the ongoing week)\n\n",
+        "Points",
+        rows,
+        None,
+    )
+    .await?;
+
+    cx.answer(&rankings).send().await?;
+
+    Ok(())
+}
+
+/// The winner polls of the chat's ongoing bet week that can still be ranked with /confidence,
+/// i.e. the games that didn't tip off yet: (poll id, "Away Team @ Home Team")
+pub async fn get_open_polls(
+    pool: &PgPool,
+    chat_id: i64,
+    bet_week_id: i32,
+) -> Result<Vec<(String, String)>, Error> {
+    Ok(query!(
+        r#"
+        SELECT polls.id, full_game_information.away_team, full_game_information.home_team
+        FROM polls
+        JOIN full_game_information ON full_game_information.game_id = polls.game_id
+        WHERE polls.chat_id = $1
+        AND polls.bet_week_id = $2
+        AND polls.poll_type = 'winner'
+        AND polls.is_void = False
+        AND full_game_information.date_time > NOW()
+        ORDER BY full_game_information.date_time ASC, polls.id ASC
+        "#,
+        chat_id,
+        bet_week_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|poll| {
+        (
+            poll.id,
+            format!(
+                "{} @ {}",
+                poll.away_team.unwrap_or_default(),
+                poll.home_team.unwrap_or_default()
+            ),
+        )
+    })
+    .collect())
+}
+
+/// Confidence values from 1 to the number of polls of the week that the user didn't already give
+/// to a game that tipped off, highest first
+pub async fn free_confidence_values(
+    pool: &PgPool,
+    user_id: i64,
+    bet_week_id: i32,
+) -> Result<Vec<i32>, Error> {
+    let number_of_polls = query!(
+        r#"
+        SELECT COUNT(*) AS number_of_polls FROM polls
+        WHERE bet_week_id = $1 AND poll_type = 'winner' AND is_void = False
+        "#,
+        bet_week_id
+    )
+    .fetch_one(pool)
+    .await?
+    .number_of_polls
+    .unwrap_or(0) as i32;
+
+    let locked_values = query!(
+        r#"
+        SELECT confidence_picks.confidence
+        FROM confidence_picks
+        JOIN polls ON polls.id = confidence_picks.poll_id
+        JOIN games ON games.id = polls.game_id
+        WHERE confidence_picks.user_id = $1
+        AND confidence_picks.bet_week_id = $2
+        AND games.date_time <= NOW()
+        "#,
+        user_id,
+        bet_week_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|pick| pick.confidence)
+    .collect::<Vec<_>>();
+
+    Ok((1..=number_of_polls)
+        .rev()
+        .filter(|confidence| !locked_values.contains(confidence))
+        .collect())
+}
+
+/// Replaces the confidence the user put in the given polls
+/// picks: (poll id, confidence)
+pub async fn set_confidence(
+    pool: &PgPool,
+    chat_id: i64,
+    user_id: i64,
+    bet_week_id: i32,
+    picks: &[(String, i32)],
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    // the values of games that didn't tip off yet are handed out again
+    query!(
+        r#"
+        DELETE FROM confidence_picks
+        WHERE user_id = $1
+        AND bet_week_id = $2
+        AND poll_id IN (
+            SELECT polls.id FROM polls JOIN games ON games.id = polls.game_id
+            WHERE polls.bet_week_id = $2 AND games.date_time > NOW()
+        )
+        "#,
+        user_id,
+        bet_week_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    for (poll_id, confidence) in picks {
+        query!(
+            r#"
+            INSERT INTO confidence_picks(chat_id, user_id, poll_id, bet_week_id, confidence) VALUES
+            ($1, $2, $3, $4, $5)
+            "#,
+            chat_id,
+            user_id,
+            poll_id,
+            bet_week_id,
+            confidence
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Shows the season standings of the chat's official ranking system
+pub async fn show_official_rankings(
     cx: &UpdateWithCx<Message>,
     pool: &PgPool,
     chat_id: i64,
 ) -> Result<(), Error> {
-    let ranking_query = query!(
-        r#"
-        SELECT 
-         first_name
-         ,last_name
-         ,username
-         ,chat_id
-         ,SUM(CASE WHEN rank_number = 1 THEN 1 ELSE 0 END)  as weeks_won
-         ,RANK() OVER (partition by chat_id ORDER BY SUM(CASE WHEN rank_number = 1 THEN 1 ELSE 0 END) DESC )
-        FROM weekly_rankings WHERE chat_id = $1 
-    GROUP BY
-    first_name
-    ,last_name
-    ,username
-    ,chat_id
-    ORDER BY weeks_won DESC;
-        "#,
-        chat_id
-
-
-    ).fetch_all(pool).await?;
-
-    let mut rankings = String::from(
-        "Standings (including current week)\n\nRank |          Name          |    Weeks Won\n--- --- --- --- --- --- --- --- --- --- ---\n",
-            );
-
-    for record in ranking_query {
-        let first_name = record.first_name.unwrap_or_else(|| "X".to_string());
-        let mut spacing = String::from("");
-
-        if let len @ 0..=13 = first_name.len() {
-            for _ in 0..(13 - len) {
-                spacing.push('\t')
-            }
-        }
-        rankings.push_str(
-            &format!(
-                "    {rank}    | {spacing} {first_name} {spacing} | \t\t\t\t\t\t\t\t\t{weeks_won}\n",
-                rank = record.rank.unwrap_or(-1),
-                first_name = first_name,
-                spacing = spacing,
-                weeks_won = record.weeks_won.unwrap_or(-1)
-            )
-            .as_str(),
-        );
+    match get_ranking_system(pool, chat_id).await? {
+        RankingSystem::Weekly => show_complete_rankings(cx, pool, chat_id).await,
+        RankingSystem::PerGame => show_all_bets_season(pool, cx, chat_id).await,
+        RankingSystem::Confidence => show_confidence_rankings(cx, pool, chat_id).await,
+        RankingSystem::UpsetBonus => show_weighted_rankings(cx, pool, chat_id).await,
     }
-
-    rankings.push('\n');
-    rankings.push_str(&get_duration_since_update().unwrap_or_default());
-
-    cx.answer(&rankings).send().await?;
-
-    Ok(())
 }
 
 pub async fn show_game_results(
@@ -839,12 +2807,12 @@ pub async fn show_game_results(
 ) -> Result<(), Error> {
     let started_games = query!(
         r#"
-        SELECT game_id,away_team, away_points, home_team, home_points, date_time
+        SELECT game_id,away_team, away_points, home_team, home_points, date_time, status, period, clock
         FROM full_game_information
         WHERE 
         NOW() AT TIME ZONE 'EST' >= date_time AT TIME ZONE 'EST'
         AND
-        game_id IN (SELECT game_id from polls join bet_weeks ON polls.bet_week_id = bet_weeks.id WHERE polls.chat_id = $1 AND week_number=$2)
+        game_id IN (SELECT game_id from polls join bet_weeks ON polls.bet_week_id = bet_weeks.id WHERE polls.chat_id = $1 AND week_number=$2 AND polls.is_void = False)
         ORDER BY date_time ASC
         "#,
         chat_id,
@@ -858,6 +2826,7 @@ pub async fn show_game_results(
         return Ok(());
     }
 
+    let time_zone = get_time_zone(pool, chat_id).await?;
     let mut game_results = String::from("Game Results:\n");
     game_results.push('\n');
 
@@ -868,14 +2837,30 @@ pub async fn show_game_results(
         let home_team = game.home_team.unwrap_or_default();
         let away_points = game.away_points.unwrap_or_default();
         let home_points = game.home_points.unwrap_or_default();
+        let tip_off = game.date_time.unwrap_or_else(chrono::Utc::now);
+        let status = GameStatus::from_db(
+            game.status.as_deref().unwrap_or_default(),
+            game.period,
+            game.clock,
+        );
         game_results.push_str(&format!(
-            "{away_points} {away_team}\n{home_points} {home_team}\n\nCorrect Bet:\n",
+            "{date} {time} - {status}\n{away_points} {away_team}\n{home_points} {home_team}\n\n",
+            date = local_date(tip_off, time_zone),
+            time = local_time(tip_off, time_zone),
+            status = status,
             away_points = away_points,
             away_team = away_team,
             home_points = home_points,
             home_team = home_team
         ));
 
+        // bets are only decided once the game is over
+        if !status.is_final() {
+            game_results.push('\n');
+            continue;
+        }
+        game_results.push_str("Correct Bet:\n");
+
         let correct_bet_users = query!(
             r#"
             SELECT first_name from users
@@ -910,9 +2895,39 @@ pub async fn show_week_rankings(
     chat_id: i64,
     week_number: i32,
 ) -> Result<(), Error> {
+    match week_rankings_message(pool, chat_id, week_number).await? {
+        Some(rankings) => {
+            cx.answer(&rankings).send().await?;
+        }
+        None => {
+            cx.answer_str("You can see the standings a couple hours after your first game is finished.\nMake sure to answer at least one poll!").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the standings table of the given week or None if nobody placed a bet yet.
+/// week_number -1 stands for the ongoing week.
+pub async fn week_rankings_message(
+    pool: &PgPool,
+    chat_id: i64,
+    week_number: i32,
+) -> Result<Option<String>, Error> {
+    match get_ranking_system(pool, chat_id).await? {
+        RankingSystem::Confidence => {
+            return confidence_week_rankings_message(pool, chat_id, week_number).await
+        }
+        RankingSystem::UpsetBonus => {
+            return weighted_week_rankings_message(pool, chat_id, week_number).await
+        }
+        RankingSystem::Weekly | RankingSystem::PerGame => (),
+    }
+
     let ranking_query = query!(
         r#"
-        SELECT first_name
+        SELECT id
+        ,first_name
         ,last_name
         ,username
         ,correct_bets_week
@@ -941,43 +2956,297 @@ pub async fn show_week_rankings(
     let week_number;
     let week_number_raw = &ranking_query.get(0);
     if week_number_raw.is_none() {
-        cx.answer_str("You can see the standings a couple hours after your first game is finished.\nMake sure to answer at least one poll!").await?;
-        return Ok(());
+        return Ok(None);
     } else {
         week_number = week_number_raw.unwrap().week_number.unwrap_or(-1);
     }
 
     let finished_games = number_of_finished_games_week(pool, chat_id, week_number).await?;
-    let mut rankings = format!("Week {week_number}\nYou get one point for every correct bet\nSend /help to see more commands\n\n\nRank |          Name          |    Points\n--- --- --- --- --- --- --- --- --- --\n",
-            week_number = week_number);
+    let rows = ranking_query
+        .into_iter()
+        .map(|record| StandingsRow {
+            user_id: record.id.unwrap_or(-1),
+            rank: record.rank_number.unwrap_or(-1),
+            first_name: record.first_name,
+            points: format!(
+                "{}/{}",
+                record.correct_bets_week.unwrap_or(-1),
+                finished_games
+            ),
+        })
+        .collect();
+
+    let totals = totals_column(pool, chat_id, Some(week_number)).await?;
+    let rankings = standings_message(
+        pool,
+        chat_id,
+        &format!("Week {week_number}\nYou get one point for every correct bet\nSend /help to see more commands\n\n\n",
+            week_number = week_number),
+        "Points",
+        rows,
+        totals,
+    )
+    .await?;
 
-    for record in ranking_query {
-        let first_name = record.first_name.unwrap_or_else(|| "X".to_string());
-        let mut spacing = String::from("");
+    Ok(Some(rankings))
+}
+
+/// Like week_rankings_message, for chats that play with confidence points
+async fn confidence_week_rankings_message(
+    pool: &PgPool,
+    chat_id: i64,
+    week_number: i32,
+) -> Result<Option<String>, Error> {
+    let ranking_query = query!(
+        r#"
+        SELECT id
+        ,first_name
+        ,confidence_points_week
+        ,week_number
+        ,rank_number
+        FROM weekly_confidence_rankings
+        WHERE
+        chat_id = $1
+        AND
+                week_number = CASE WHEN $2 = -1 THEN (SELECT MAX(week_number)
+                                FROM weekly_confidence_rankings
+                                WHERE chat_id = $1
+                                AND start_date AT TIME ZONE 'EST' <= NOW() AT TIME ZONE 'EST' - INTERVAL '1 DAYS')
+                                ELSE $2
+                                END
+        ORDER BY confidence_points_week DESC;
+        "#,
+        chat_id,
+        week_number
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let week_number = match ranking_query.get(0) {
+        Some(record) => record.week_number.unwrap_or(-1),
+        None => return Ok(None),
+    };
+
+    let rows = ranking_query
+        .into_iter()
+        .map(|record| StandingsRow {
+            user_id: record.id.unwrap_or(-1),
+            rank: record.rank_number.unwrap_or(-1),
+            first_name: record.first_name,
+            points: record.confidence_points_week.unwrap_or(-1).to_string(),
+        })
+        .collect();
+
+    let rankings = standings_message(
+        pool,
+        chat_id,
+        &format!("Week {week_number}\nA correct pick earns the confidence you put in it (/confidence)\nSend /help to see more commands\n\n\n",
+            week_number = week_number),
+        "Points",
+        rows,
+        None,
+    )
+    .await?;
+
+    Ok(Some(rankings))
+}
+
+/// Like week_rankings_message, for chats that play with the upset bonus
+async fn weighted_week_rankings_message(
+    pool: &PgPool,
+    chat_id: i64,
+    week_number: i32,
+) -> Result<Option<String>, Error> {
+    let ranking_query = query!(
+        r#"
+        SELECT id
+        ,first_name
+        ,weighted_points_week
+        ,week_number
+        ,rank_number
+        FROM weekly_weighted_rankings
+        WHERE
+        chat_id = $1
+        AND
+                week_number = CASE WHEN $2 = -1 THEN (SELECT MAX(week_number)
+                                FROM weekly_weighted_rankings
+                                WHERE chat_id = $1
+                                AND start_date AT TIME ZONE 'EST' <= NOW() AT TIME ZONE 'EST' - INTERVAL '1 DAYS')
+                                ELSE $2
+                                END
+        ORDER BY weighted_points_week DESC;
+        "#,
+        chat_id,
+        week_number
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let week_number = match ranking_query.get(0) {
+        Some(record) => record.week_number.unwrap_or(-1),
+        None => return Ok(None),
+    };
+
+    let rows = ranking_query
+        .into_iter()
+        .map(|record| StandingsRow {
+            user_id: record.id.unwrap_or(-1),
+            rank: record.rank_number.unwrap_or(-1),
+            first_name: record.first_name,
+            points: record
+                .weighted_points_week
+                .map(|points| points.to_string())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    let rankings = standings_message(
+        pool,
+        chat_id,
+        &format!("Week {week_number}\nYou get one point for every correct bet, plus a bonus for every underdog you got right\nSend /help to see more commands\n\n\n",
+            week_number = week_number),
+        "Points",
+        rows,
+        None,
+    )
+    .await?;
+
+    Ok(Some(rankings))
+}
 
-        if let len @ 0..=13 = first_name.len() {
-            for _ in 0..(13 - len) {
-                spacing.push('\t')
+/// Sends the final standings of every bet week that is over to its chat, together with the
+/// winners of the week. Each week is only announced once.
+pub async fn broadcast_weekly_results(pool: &PgPool, bot: &teloxide::Bot) -> Result<(), Error> {
+    let today = east_coast_date_today()?;
+
+    // a week is over when its end date has passed and every game of the week is finished or
+    // won't be played anymore
+    let finished_weeks = query!(
+        r#"
+        SELECT bet_weeks.id, bet_weeks.chat_id, bet_weeks.week_number
+        FROM bet_weeks
+        JOIN chats ON chats.id = bet_weeks.chat_id
+        WHERE chats.is_active = True
+        AND bet_weeks.results_sent = False
+        AND bet_weeks.end_date < $1
+        AND NOT EXISTS(
+            SELECT *
+            FROM polls JOIN games ON games.id = polls.game_id
+            WHERE polls.bet_week_id = bet_weeks.id
+            AND polls.is_void = False
+            AND games.status NOT IN ('final', 'final_ot', 'postponed', 'cancelled')
+        )
+        ORDER BY bet_weeks.week_number ASC
+        "#,
+        today
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for week in finished_weeks {
+        let chat_id = week.chat_id.unwrap_or(-1);
+        let week_number = week.week_number.unwrap_or(-1);
+
+        // mark the week first, so the results are never sent twice, even if sending fails
+        query!(
+            "UPDATE bet_weeks SET results_sent = True WHERE id = $1",
+            week.id
+        )
+        .execute(pool)
+        .await?;
+
+        let rankings = match week_rankings_message(pool, chat_id, week_number).await? {
+            Some(rankings) => rankings,
+            // nobody in the chat placed a bet that week
+            None => continue,
+        };
+
+        let winners = match get_ranking_system(pool, chat_id).await? {
+            RankingSystem::Confidence => query!(
+                r#"
+                SELECT first_name
+                FROM weekly_confidence_rankings
+                WHERE chat_id = $1
+                AND week_number = $2
+                AND rank_number = 1
+                AND confidence_points_week > 0
+                ORDER BY first_name
+                "#,
+                chat_id,
+                week_number
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|winner| winner.first_name)
+            .collect::<Vec<_>>(),
+            RankingSystem::UpsetBonus => query!(
+                r#"
+                SELECT first_name
+                FROM weekly_weighted_rankings
+                WHERE chat_id = $1
+                AND week_number = $2
+                AND rank_number = 1
+                AND weighted_points_week > 0
+                ORDER BY first_name
+                "#,
+                chat_id,
+                week_number
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|winner| winner.first_name)
+            .collect::<Vec<_>>(),
+            RankingSystem::Weekly | RankingSystem::PerGame => query!(
+                r#"
+                SELECT first_name
+                FROM weekly_rankings
+                WHERE chat_id = $1
+                AND week_number = $2
+                AND rank_number = 1
+                AND correct_bets_week > 0
+                ORDER BY first_name
+                "#,
+                chat_id,
+                week_number
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|winner| winner.first_name)
+            .collect::<Vec<_>>(),
+        }
+        .into_iter()
+        .map(|first_name| first_name.unwrap_or_else(|| "X".to_string()))
+        .collect::<Vec<_>>();
+
+        let announcement = match winners.as_slice() {
+            [] => format!("Week {} is over! Nobody got a bet right...", week_number),
+            [winner] => format!("Week {} is over!\n{} wins the week!", week_number, winner),
+            _ => format!(
+                "Week {} is over!\nIt's a tie! {} win the week!",
+                week_number,
+                winners.join(" and ")
+            ),
+        };
+
+        for message in [rankings, announcement].iter() {
+            match bot.send_message(chat_id, message).send().await {
+                Ok(_) => (),
+                Err(e) => {
+                    log::error!(
+                        "could not send the results of week {} to chat {}: {}",
+                        week_number,
+                        chat_id,
+                        e
+                    );
+                    break;
+                }
             }
         }
-        rankings.push_str(
-            &format!(
-                "    {rank}    | {spacing} {first_name} {spacing} | \t\t\t\t{correct_bets_week}/{finished_games}\n",
-                rank = record.rank_number.unwrap_or(-1),
-                first_name = first_name,
-                spacing = spacing,
-                finished_games = finished_games,
-                correct_bets_week = record.correct_bets_week.unwrap_or(-1)
-            )
-            .as_str(),
-        );
     }
 
-    rankings.push('\n');
-    rankings.push_str(&get_duration_since_update().unwrap_or_default());
-
-    cx.answer(&rankings).send().await?;
-
     Ok(())
 }
 
@@ -1019,7 +3288,7 @@ fn _get_duration_since_update() -> String {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
     id: i32,
     away_team_id: i32,
@@ -1027,9 +3296,321 @@ pub struct Game {
     home_team_id: i32,
     home_team: String,
     srs_sum: f64,
-    pretty_date_time: String,
-    date_string: String,
-    time_string: String,
+    date_time: chrono::DateTime<Utc>,
+    game_quality: f64,
+    /// the battle of the supreme tank commanders
+    tank_game: bool,
+}
+
+/// Decides which games of a bet week are sent to a chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    /// every game of the week
+    Full,
+    /// the best games of the week (10 by default) plus one battle of the tank commanders
+    BestOf(i64),
+    /// like BestOf, but the chat picks the number of games itself
+    Custom(i64),
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        Self::BestOf(10)
+    }
+}
+
+impl GameMode {
+    /// Most polls we send per week in custom mode, so the chat doesn't drown in polls
+    pub const MAX_CUSTOM_GAMES: i64 = 20;
+
+    /// e.g. "best_of_10", part of the key of the cached games
+    fn key(&self) -> String {
+        match self {
+            Self::Full => "full".to_string(),
+            Self::BestOf(n) => format!("best_of_{}", n),
+            Self::Custom(n) => format!("custom_{}", n),
+        }
+    }
+
+    /// id in the game_modes table
+    fn id(&self) -> i32 {
+        match self {
+            Self::Full => 1,
+            Self::BestOf(_) => 2,
+            Self::Custom(_) => 3,
+        }
+    }
+
+    /// The games a chat gets each week for /help, e.g. "every NBA game of the week to bet on"
+    pub fn games_description(&self) -> String {
+        match self {
+            Self::Full => "every NBA game of the week to bet on".to_string(),
+            Self::BestOf(n) | Self::Custom(n) => format!(
+                "{total} NBA games to bet on each week, {n} good {ones} and one battle between the supreme tank commanders",
+                total = n + 1,
+                n = n,
+                ones = if *n == 1 { "one" } else { "ones" }
+            ),
+        }
+    }
+
+    /// Parses the arguments of the /mode command, e.g. ["full"] or ["custom", "5"]
+    pub fn parse(args: &[&str]) -> Option<Self> {
+        match args {
+            ["full"] => Some(Self::Full),
+            ["best_of"] => Some(Self::default()),
+            ["custom", number_of_games] => match number_of_games.parse::<i64>() {
+                Ok(n) if (1..=Self::MAX_CUSTOM_GAMES).contains(&n) => Some(Self::Custom(n)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for GameMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full => write!(f, "full (every game of the week)"),
+            Self::BestOf(n) => write!(f, "best_of ({} best games + 1 tank battle)", n),
+            Self::Custom(n) => write!(f, "custom ({} best games + 1 tank battle)", n),
+        }
+    }
+}
+
+/// Decides who is the overall winner of a chat's season
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankingSystem {
+    /// most weeks won (/full_standings)
+    Weekly,
+    /// most correct bets over the whole season (/all_bets)
+    PerGame,
+    /// most confidence points over the whole season (/confidence_standings), every correct pick
+    /// earns the confidence the player put in it with /confidence
+    Confidence,
+    /// most points over the whole season (/upset_standings), correct picks on the underdog earn
+    /// bonus points that grow with the difference in team strength
+    UpsetBonus,
+}
+
+impl Default for RankingSystem {
+    fn default() -> Self {
+        Self::Weekly
+    }
+}
+
+impl RankingSystem {
+    /// id in the ranking_systems table
+    fn id(&self) -> i32 {
+        match self {
+            Self::Weekly => 1,
+            Self::PerGame => 2,
+            Self::Confidence => 3,
+            Self::UpsetBonus => 4,
+        }
+    }
+
+    /// Parses the argument of the /ranking command
+    pub fn parse(args: &[&str]) -> Option<Self> {
+        match args {
+            ["weekly"] => Some(Self::Weekly),
+            ["per_game"] => Some(Self::PerGame),
+            ["confidence"] => Some(Self::Confidence),
+            ["upset_bonus"] => Some(Self::UpsetBonus),
+            _ => None,
+        }
+    }
+
+    /// The command that shows the season standings of this ranking system
+    pub fn command(&self) -> &'static str {
+        match self {
+            Self::Weekly => "/full_standings",
+            Self::PerGame => "/all_bets",
+            Self::Confidence => "/confidence_standings",
+            Self::UpsetBonus => "/upset_standings",
+        }
+    }
+}
+
+impl std::fmt::Display for RankingSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Weekly => write!(f, "weekly (most weeks won)"),
+            Self::PerGame => write!(f, "per_game (most correct bets overall)"),
+            Self::Confidence => write!(f, "confidence (most confidence points overall)"),
+            Self::UpsetBonus => write!(f, "upset_bonus (most points overall, underdogs earn more)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameStatus {
+    Scheduled,
+    /// period 1 to 4 are the quarters, 5 and up the overtimes
+    InProgress {
+        period: i32,
+        clock: String,
+    },
+    Final,
+    FinalOt,
+    Postponed,
+    Cancelled,
+}
+
+impl GameStatus {
+    /// value of games.status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Scheduled => "scheduled",
+            Self::InProgress { .. } => "in_progress",
+            Self::Final => "final",
+            Self::FinalOt => "final_ot",
+            Self::Postponed => "postponed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn from_db(status: &str, period: Option<i32>, clock: Option<String>) -> Self {
+        match status {
+            "in_progress" => Self::InProgress {
+                period: period.unwrap_or(1),
+                clock: clock.unwrap_or_default(),
+            },
+            "final" => Self::Final,
+            "final_ot" => Self::FinalOt,
+            "postponed" => Self::Postponed,
+            "cancelled" => Self::Cancelled,
+            _ => Self::Scheduled,
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Final | Self::FinalOt)
+    }
+}
+
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scheduled => write!(f, "Scheduled"),
+            Self::InProgress { period, clock } => match period {
+                1..=4 => write!(f, "LIVE Q{} {}", period, clock),
+                5 => write!(f, "LIVE OT {}", clock),
+                _ => write!(f, "LIVE {}OT {}", period - 4, clock),
+            },
+            Self::Final => write!(f, "Final"),
+            Self::FinalOt => write!(f, "Final/OT"),
+            Self::Postponed => write!(f, "Postponed"),
+            Self::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollType {
+    /// pick the winner of the game, against the spread if the poll has one
+    Winner,
+    /// pick over or under the total points of the game
+    Total,
+}
+
+impl PollType {
+    /// value of polls.poll_type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Winner => "winner",
+            Self::Total => "total",
+        }
+    }
+}
+
+/// What became of a click on a button of a game sent with keyboard bets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardBet {
+    /// the pick is stored, a previous pick of the other team was replaced
+    Added,
+    /// the user picked that team already
+    Unchanged,
+    /// the game started or the poll was closed or voided
+    Closed,
+}
+
+#[derive(Debug)]
+pub struct Season {
+    pub id: i32,
+    pub year: i32,
+    pub regular_season_start: chrono::NaiveDate,
+    pub regular_season_end: chrono::NaiveDate,
+    pub play_in_start: chrono::NaiveDate,
+    pub playoffs_start: chrono::NaiveDate,
+    pub playoffs_end: chrono::NaiveDate,
+}
+
+impl Season {
+    /// Polls for a new bet week are only sent if the whole week is part of the regular season
+    pub fn last_poll_date(&self) -> chrono::NaiveDate {
+        self.regular_season_end - Duration::days(7)
+    }
+
+    /// The day after the regular season ended, that's when the final standings are announced
+    pub fn season_over_date(&self) -> chrono::NaiveDate {
+        self.regular_season_end + Duration::days(1)
+    }
+
+    /// The day after the finals, there is nothing left to do for the bot until the next season
+    pub fn playoffs_over_date(&self) -> chrono::NaiveDate {
+        self.playoffs_end + Duration::days(1)
+    }
+
+    /// First and last day of the bet week that starts tomorrow
+    pub fn upcoming_bet_week(&self) -> Result<(chrono::NaiveDate, chrono::NaiveDate), Error> {
+        let tomorrow = east_coast_date_in_x_days(1, false)?;
+        let days = self.phase(tomorrow).bet_week_days().max(1);
+
+        Ok((tomorrow, east_coast_date_in_x_days(days, false)?))
+    }
+
+    pub fn phase(&self, date: chrono::NaiveDate) -> SeasonPhase {
+        if date < self.play_in_start {
+            SeasonPhase::RegularSeason
+        } else if date < self.playoffs_start {
+            SeasonPhase::PlayIn
+        } else if date <= self.playoffs_end {
+            SeasonPhase::Playoffs
+        } else {
+            SeasonPhase::Over
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeasonPhase {
+    RegularSeason,
+    PlayIn,
+    Playoffs,
+    Over,
+}
+
+impl SeasonPhase {
+    /// Number of days a bet week lasts. Play-in and playoff games are only scheduled a day or
+    /// two ahead, so their polls are sent every day.
+    pub fn bet_week_days(&self) -> i64 {
+        match self {
+            Self::RegularSeason => 7,
+            Self::PlayIn | Self::Playoffs => 1,
+            Self::Over => 0,
+        }
+    }
+}
+
+/// e.g. "First Round"
+pub fn playoff_round_name(round: i32) -> &'static str {
+    match round {
+        1 => "First Round",
+        2 => "Conference Semifinals",
+        3 => "Conference Finals",
+        _ => "Finals",
+    }
 }
 
 #[derive(Debug)]